@@ -292,6 +292,27 @@ where
     }
 }
 
+impl Serialize for ChunkCoord {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        TileCoord(self.0).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkCoord
+where
+    Self: Sized,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        TileCoord::deserialize(deserializer).map(|v| Self(v.0))
+    }
+}
+
 impl From<TileHex> for TileCoord {
     fn from(value: TileHex) -> Self {
         Self(value)
@@ -5,11 +5,11 @@ use serde::{Deserialize, Serialize};
 
 use automancy_defs::id::{Id, IdRaw, Interner};
 
-use crate::data::item::Item;
+use crate::data::item::{item_match, Item};
 use crate::data::stack::ItemAmount;
-use crate::ResourceManager;
+use crate::{ResourceManager, RESOURCE_MAN};
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Inventory(BTreeMap<Id, ItemAmount>);
 
 impl Deref for Inventory {
@@ -57,7 +57,11 @@ impl Inventory {
 
         let taking = amount.min(stored);
 
-        self.insert(id, stored - taking);
+        if taking == stored {
+            self.0.remove(&id);
+        } else {
+            self.insert(id, stored - taking);
+        }
 
         Some(taking)
     }
@@ -82,6 +86,40 @@ impl Inventory {
         self.take(item.id, amount)
     }
 
+    /// Counts the stored items that match the id, which can be a tag.
+    pub fn count_matching(&self, resource_man: &ResourceManager, id: Id) -> ItemAmount {
+        self.0
+            .iter()
+            .filter(|(item, _)| item_match(resource_man, **item, id))
+            .map(|(_, amount)| *amount)
+            .sum()
+    }
+
+    /// Takes from whichever stored items match the id, which can be a tag. Returns how many could not be taken.
+    pub fn take_matching(
+        &mut self,
+        resource_man: &ResourceManager,
+        id: Id,
+        mut amount: ItemAmount,
+    ) -> ItemAmount {
+        for (item, stored) in self.0.iter_mut() {
+            if amount <= 0 {
+                break;
+            }
+
+            if item_match(resource_man, *item, id) {
+                let taking = amount.min(*stored);
+
+                *stored -= taking;
+                amount -= taking;
+            }
+        }
+
+        self.0.retain(|_, stored| *stored != 0);
+
+        amount
+    }
+
     pub fn to_raw(&self, interner: &Interner) -> InventoryRaw {
         InventoryRaw(
             self.0
@@ -97,9 +135,31 @@ impl Inventory {
     }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+pub fn rhai_count_matching(inventory: &mut Inventory, id: Id) -> ItemAmount {
+    inventory.count_matching(RESOURCE_MAN.read().unwrap().as_ref().unwrap(), id)
+}
+
+pub fn rhai_take_matching(inventory: &mut Inventory, id: Id, amount: ItemAmount) -> ItemAmount {
+    inventory.take_matching(RESOURCE_MAN.read().unwrap().as_ref().unwrap(), id, amount)
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct InventoryRaw(Vec<(IdRaw, ItemAmount)>);
 
+impl Deref for InventoryRaw {
+    type Target = Vec<(IdRaw, ItemAmount)>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<(IdRaw, ItemAmount)>> for InventoryRaw {
+    fn from(value: Vec<(IdRaw, ItemAmount)>) -> Self {
+        Self(value)
+    }
+}
+
 impl InventoryRaw {
     pub fn into_inner(self) -> Vec<(IdRaw, ItemAmount)> {
         self.0
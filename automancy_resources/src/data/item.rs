@@ -4,13 +4,19 @@ use rhai::Dynamic;
 
 use automancy_defs::id::Id;
 
-use crate::data::stack::ItemStack;
+use crate::data::stack::{ItemAmount, ItemStack};
 use crate::{ResourceManager, RESOURCE_MAN};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct Item {
     pub id: Id,
     pub model: Id,
+    /// what the item is grouped under
+    pub category: Option<Id>,
+    /// how many of the item fit in one slot
+    pub max_stack: Option<ItemAmount>,
+    /// the translation key of the item's description
+    pub description: Option<Id>,
 }
 
 impl PartialOrd<Self> for Item {
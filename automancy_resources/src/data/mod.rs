@@ -10,21 +10,23 @@ use automancy_defs::hashbrown::HashMap;
 use automancy_defs::id::{Id, IdRaw, Interner};
 
 use crate::data::inventory::{Inventory, InventoryRaw};
-use crate::data::stack::ItemAmount;
+use crate::data::stack::{ItemAmount, ItemStack};
 use crate::ResourceManager;
 
 pub mod inventory;
 pub mod item;
+pub mod packed;
 pub mod stack;
 
 /// Represents the data a tile entity holds. This data is given to functions.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Data {
     Inventory(Inventory),
     Coord(TileCoord),
     VecCoord(Vec<TileCoord>),
     Id(Id),
     VecId(Vec<Id>),
+    VecStack(Vec<ItemStack>),
     Amount(ItemAmount),
     Bool(bool),
 }
@@ -83,6 +85,13 @@ impl Data {
         None
     }
 
+    pub fn as_vec_stack_mut(&mut self) -> Option<&mut Vec<ItemStack>> {
+        if let Self::VecStack(v) = self {
+            return Some(v);
+        }
+        None
+    }
+
     pub fn as_amount_mut(&mut self) -> Option<&mut ItemAmount> {
         if let Self::Amount(v) = self {
             return Some(v);
@@ -132,6 +141,13 @@ impl Data {
         None
     }
 
+    pub fn as_vec_stack(&self) -> Option<&Vec<ItemStack>> {
+        if let Self::VecStack(v) = self {
+            return Some(v);
+        }
+        None
+    }
+
     pub fn as_amount(&self) -> Option<&ItemAmount> {
         if let Self::Amount(v) = self {
             return Some(v);
@@ -181,6 +197,13 @@ impl Data {
         Dynamic::UNIT
     }
 
+    pub fn rhai_vec_stack(self) -> Dynamic {
+        if let Self::VecStack(v) = self {
+            return Dynamic::from(v);
+        }
+        Dynamic::UNIT
+    }
+
     pub fn rhai_amount(self) -> Dynamic {
         if let Self::Amount(v) = self {
             return Dynamic::from_int(v);
@@ -195,6 +218,7 @@ impl Data {
             v @ Data::VecCoord(_) => v.rhai_vec_coord(),
             v @ Data::Id(_) => v.rhai_id(),
             v @ Data::VecId(_) => v.rhai_vec_id(),
+            v @ Data::VecStack(_) => v.rhai_vec_stack(),
             v @ Data::Amount(_) => v.rhai_amount(),
             v @ Data::Bool(_) => v.rhai_bool(),
         }
@@ -217,6 +241,8 @@ impl TryFrom<Dynamic> for Data {
             Ok(Data::Id(value.cast()))
         } else if ty == TypeId::of::<Vec<Id>>() {
             Ok(Data::VecId(value.cast()))
+        } else if ty == TypeId::of::<Vec<ItemStack>>() {
+            Ok(Data::VecStack(value.cast()))
         } else if ty == TypeId::of::<ItemAmount>() {
             Ok(Data::Amount(value.cast()))
         } else if ty == TypeId::of::<bool>() {
@@ -227,7 +253,7 @@ impl TryFrom<Dynamic> for Data {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct DataMap(BTreeMap<Id, Data>);
 
 impl From<DataMap> for BTreeMap<Id, Data> {
@@ -266,26 +292,33 @@ impl DataMap {
             self.0
                 .iter()
                 .flat_map(|(key, value)| {
-                    interner.resolve(*key).map(|key| {
-                        (
+                    interner.resolve(*key).and_then(|key| {
+                        Some((
                             key.to_string(),
                             match value {
                                 Data::Inventory(v) => DataRaw::Inventory(v.to_raw(interner)),
                                 Data::Coord(v) => DataRaw::Coord(*v),
                                 Data::VecCoord(v) => DataRaw::VecCoord(v.clone()),
-                                Data::Id(v) => {
-                                    // TODO dont unwrap
-                                    DataRaw::Id(IdRaw::parse(interner.resolve(*v).unwrap()))
-                                }
+                                Data::Id(v) => DataRaw::Id(IdRaw::parse(interner.resolve(*v)?)),
                                 Data::VecId(v) => DataRaw::VecId(
                                     v.iter()
-                                        .map(|id| IdRaw::parse(interner.resolve(*id).unwrap()))
+                                        .flat_map(|id| interner.resolve(*id))
+                                        .map(IdRaw::parse)
+                                        .collect(),
+                                ),
+                                Data::VecStack(v) => DataRaw::VecStack(
+                                    v.iter()
+                                        .flat_map(|stack| {
+                                            interner
+                                                .resolve(stack.item.id)
+                                                .map(|id| (IdRaw::parse(id), stack.amount))
+                                        })
                                         .collect(),
                                 ),
                                 Data::Amount(v) => DataRaw::Amount(*v),
                                 Data::Bool(v) => DataRaw::Bool(*v),
                             },
-                        )
+                        ))
                     })
                 })
                 .collect(),
@@ -298,6 +331,7 @@ impl DataMap {
         match ty.as_str() {
             "inventory" => Some(Data::new_inventory()),
             "veccoord" => Some(Data::new_vec_coord()),
+            "vecstack" => Some(Data::VecStack(Default::default())),
             "bool" => Some(Data::Bool(false)),
             "amount" => Some(Data::Amount(0)),
             "coord" => Some(Data::Coord(TileCoord::ZERO)),
@@ -326,18 +360,19 @@ impl DataMap {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DataRaw {
     Inventory(InventoryRaw),
     Coord(TileCoord),
     VecCoord(Vec<TileCoord>),
     Id(IdRaw),
     VecId(Vec<IdRaw>),
+    VecStack(Vec<(IdRaw, ItemAmount)>),
     Amount(ItemAmount),
     Bool(bool),
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DataMapRaw(HashMap<String, DataRaw>);
 
 impl From<DataMapRaw> for HashMap<String, DataRaw> {
@@ -357,6 +392,10 @@ impl DataMapRaw {
         self.0
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn intern_to_data(&self, resource_man: &mut ResourceManager) -> DataMap {
         DataMap(
             self.0
@@ -376,6 +415,9 @@ impl DataMapRaw {
                                     .map(|id| resource_man.interner.get_or_intern(id.to_string()))
                                     .collect(),
                             ),
+                            DataRaw::VecStack(v) => {
+                                Data::VecStack(Self::to_stacks(v, resource_man))
+                            }
                             DataRaw::Amount(v) => Data::Amount(*v),
                             DataRaw::Bool(v) => Data::Bool(*v),
                         },
@@ -385,40 +427,141 @@ impl DataMapRaw {
         )
     }
 
-    pub fn to_data(&self, resource_man: &ResourceManager) -> DataMap {
-        DataMap(
-            self.0
-                .iter()
-                .flat_map(|(key, value)| {
-                    resource_man
-                        .interner
-                        .get(IdRaw::parse(key).to_string())
-                        .map(|key| {
-                            (
-                                key,
-                                match value {
-                                    DataRaw::Inventory(v) => {
-                                        Data::Inventory(v.to_inventory(resource_man))
-                                    }
-                                    DataRaw::Coord(v) => Data::Coord(*v),
-                                    DataRaw::VecCoord(v) => Data::VecCoord(v.clone()),
-                                    DataRaw::Id(v) => {
-                                        Data::Id(resource_man.interner.get(v.to_string()).unwrap())
-                                    }
-                                    DataRaw::VecId(v) => Data::VecId(
-                                        v.iter()
-                                            .flat_map(|id| {
-                                                resource_man.interner.get(id.to_string())
-                                            })
-                                            .collect(),
-                                    ),
-                                    DataRaw::Amount(v) => Data::Amount(*v),
-                                    DataRaw::Bool(v) => Data::Bool(*v),
-                                },
-                            )
-                        })
-                })
-                .collect(),
-        )
+    /// Reads the data back. Entries and items whose ids are not loaded are given back separately as they were saved, so that they can be saved again.
+    pub fn to_data(&self, resource_man: &ResourceManager) -> (DataMap, DataMapRaw) {
+        let interner = &resource_man.interner;
+
+        let mut data = DataMap::default();
+        let mut unknown = HashMap::new();
+
+        for (name, value) in &self.0 {
+            let Some(key) = interner.get(IdRaw::parse(name).to_string()) else {
+                unknown.insert(name.clone(), value.clone());
+
+                continue;
+            };
+
+            let (value, rest) = match value {
+                DataRaw::Inventory(v) => {
+                    let (known, rest) = split_known(
+                        v.iter(),
+                        |(item, amount)| Some((interner.get(item.to_string())?, *amount)),
+                        |(item, amount)| Some((item.clone(), *amount)),
+                    );
+
+                    let mut inventory = Inventory::default();
+                    inventory.extend(known);
+
+                    (
+                        Some(Data::Inventory(inventory)),
+                        (!rest.is_empty()).then_some(DataRaw::Inventory(InventoryRaw::from(rest))),
+                    )
+                }
+                DataRaw::Coord(v) => (Some(Data::Coord(*v)), None),
+                DataRaw::VecCoord(v) => (Some(Data::VecCoord(v.clone())), None),
+                DataRaw::Id(v) => match interner.get(v.to_string()) {
+                    Some(id) => (Some(Data::Id(id)), None),
+                    None => (None, Some(DataRaw::Id(v.clone()))),
+                },
+                DataRaw::VecId(v) => {
+                    let (known, rest) =
+                        split_known(v, |id| interner.get(id.to_string()), |id| Some(id.clone()));
+
+                    (
+                        Some(Data::VecId(known)),
+                        (!rest.is_empty()).then_some(DataRaw::VecId(rest)),
+                    )
+                }
+                DataRaw::VecStack(v) => {
+                    let (known, rest) = split_known(
+                        v,
+                        |(item, amount)| {
+                            Some(ItemStack {
+                                item: resource_man
+                                    .registry
+                                    .item(interner.get(item.to_string())?)
+                                    .cloned()?,
+                                amount: *amount,
+                            })
+                        },
+                        |(item, amount)| Some((item.clone(), *amount)),
+                    );
+
+                    (
+                        Some(Data::VecStack(known)),
+                        (!rest.is_empty()).then_some(DataRaw::VecStack(rest)),
+                    )
+                }
+                DataRaw::Amount(v) => (Some(Data::Amount(*v)), None),
+                DataRaw::Bool(v) => (Some(Data::Bool(*v)), None),
+            };
+
+            if let Some(value) = value {
+                data.insert(key, value);
+            }
+
+            if let Some(rest) = rest {
+                unknown.insert(name.clone(), rest);
+            }
+        }
+
+        (data, DataMapRaw(unknown))
+    }
+
+    /// Adds back the entries and items that were given back separately when the data was read. Entries that were set again since keep their new value, though lists of items get the items added back.
+    pub fn merge(&mut self, other: &DataMapRaw) {
+        for (key, value) in &other.0 {
+            match self.0.get_mut(key) {
+                Some(existing) => match (existing, value) {
+                    (DataRaw::Inventory(a), DataRaw::Inventory(b)) => {
+                        *a = InventoryRaw::from(
+                            a.iter().chain(b.iter()).cloned().collect::<Vec<_>>(),
+                        );
+                    }
+                    (DataRaw::VecId(a), DataRaw::VecId(b)) => a.extend(b.iter().cloned()),
+                    (DataRaw::VecStack(a), DataRaw::VecStack(b)) => a.extend(b.iter().cloned()),
+                    _ => {}
+                },
+                None => {
+                    self.0.insert(key.clone(), value.clone());
+                }
+            }
+        }
     }
+
+    /// Reads saved item stacks back. Stacks of items that no longer exist are dropped.
+    fn to_stacks(raw: &[(IdRaw, ItemAmount)], resource_man: &ResourceManager) -> Vec<ItemStack> {
+        raw.iter()
+            .flat_map(|(id, amount)| {
+                resource_man
+                    .interner
+                    .get(id.to_string())
+                    .and_then(|id| resource_man.registry.item(id).cloned())
+                    .map(|item| ItemStack {
+                        item,
+                        amount: *amount,
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Splits saved items into those whose ids are loaded, and those that are kept as they were saved.
+pub(crate) fn split_known<A: Copy, T, R>(
+    items: impl IntoIterator<Item = A>,
+    resolve: impl Fn(A) -> Option<T>,
+    keep: impl Fn(A) -> Option<R>,
+) -> (Vec<T>, Vec<R>) {
+    let mut known = Vec::new();
+    let mut rest = Vec::new();
+
+    for item in items {
+        if let Some(resolved) = resolve(item) {
+            known.push(resolved);
+        } else if let Some(kept) = keep(item) {
+            rest.push(kept);
+        }
+    }
+
+    (known, rest)
 }
@@ -0,0 +1,333 @@
+use serde::{Deserialize, Serialize};
+
+use automancy_defs::coord::TileCoord;
+use automancy_defs::hashbrown::HashMap;
+use automancy_defs::id::{Id, IdRaw, Interner};
+
+use crate::data::inventory::{Inventory, InventoryRaw};
+use crate::data::stack::{ItemAmount, ItemStack};
+use crate::data::{split_known, Data, DataMap, DataMapRaw, DataRaw};
+use crate::ResourceManager;
+
+/// The index of an id in a key table.
+pub type Key = u32;
+
+/// Gives every id written into a save a small key, so that each id is only written out once.
+#[derive(Debug, Default)]
+pub struct KeyTable {
+    keys: Vec<String>,
+    indices: HashMap<Id, Key>,
+    names: HashMap<String, Key>,
+    taken: usize,
+}
+
+impl KeyTable {
+    /// Gets the key of the id, adding it to the table if it is new. None if the id is not interned.
+    pub fn key(&mut self, interner: &Interner, id: Id) -> Option<Key> {
+        if let Some(key) = self.indices.get(&id) {
+            return Some(*key);
+        }
+
+        let key = self.key_raw(interner.resolve(id)?);
+        self.indices.insert(id, key);
+
+        Some(key)
+    }
+
+    /// Gets the key of the id written out, adding it to the table if it is new. Used for ids that are not loaded.
+    pub fn key_raw(&mut self, id: &str) -> Key {
+        if let Some(key) = self.names.get(id) {
+            return *key;
+        }
+
+        let key = self.keys.len() as Key;
+
+        self.keys.push(id.to_string());
+        self.names.insert(id.to_string(), key);
+
+        key
+    }
+
+    /// Gets the ids added to the table since this was last called, in the order of their keys. Used to save each id along with the first chunk that uses it.
+    pub fn take_new(&mut self) -> Vec<String> {
+        let new = self.keys[self.taken..].to_vec();
+        self.taken = self.keys.len();
+
+        new
+    }
+
+    /// Gets the ids in the table, in the order of their keys.
+    pub fn into_keys(self) -> Vec<String> {
+        self.keys
+    }
+}
+
+/// Resolves the keys read from a save back to ids. Keys of ids that are not loaded resolve to None.
+#[derive(Debug, Default)]
+pub struct KeyLookup {
+    ids: Vec<Option<Id>>,
+    names: Vec<String>,
+}
+
+impl KeyLookup {
+    pub fn new(keys: &[String], interner: &Interner) -> Self {
+        Self {
+            ids: keys.iter().map(|key| interner.get(key.as_str())).collect(),
+            names: keys.to_vec(),
+        }
+    }
+
+    /// Adds the ids of the next keys, for saves that give their keys bit by bit.
+    pub fn extend(&mut self, keys: &[String], interner: &Interner) {
+        self.ids
+            .extend(keys.iter().map(|key| interner.get(key.as_str())));
+        self.names.extend_from_slice(keys);
+    }
+
+    /// Gets the ids the keys stand for written out, in the order of their keys.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn get(&self, key: Key) -> Option<Id> {
+        self.ids.get(key as usize).cloned().flatten()
+    }
+
+    /// Gets the id the key stands for written out, whether it is loaded or not.
+    pub fn name(&self, key: Key) -> Option<IdRaw> {
+        self.names.get(key as usize).map(|name| IdRaw::parse(name))
+    }
+}
+
+/// The compact form of [`Data`], with ids replaced by keys.
+///
+/// Saves refer to the variants by their position, so new variants must only be appended, and the existing ones never removed, reordered or changed.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum DataPacked {
+    Inventory(Vec<(Key, ItemAmount)>),
+    Coord(TileCoord),
+    VecCoord(Vec<TileCoord>),
+    Id(Key),
+    VecId(Vec<Key>),
+    VecStack(Vec<(Key, ItemAmount)>),
+    Amount(ItemAmount),
+    Bool(bool),
+}
+
+/// The compact form of [`DataMap`], with ids replaced by keys.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DataMapPacked(Vec<(Key, DataPacked)>);
+
+impl DataMap {
+    pub fn to_packed(&self, interner: &Interner, keys: &mut KeyTable) -> DataMapPacked {
+        DataMapPacked(
+            self.iter()
+                .flat_map(|(key, value)| {
+                    let key = keys.key(interner, *key)?;
+
+                    let value = match value {
+                        Data::Inventory(v) => DataPacked::Inventory(
+                            v.iter()
+                                .filter(|(_, amount)| **amount > 0)
+                                .flat_map(|(item, amount)| {
+                                    keys.key(interner, *item).map(|item| (item, *amount))
+                                })
+                                .collect(),
+                        ),
+                        Data::Coord(v) => DataPacked::Coord(*v),
+                        Data::VecCoord(v) => DataPacked::VecCoord(v.clone()),
+                        Data::Id(v) => DataPacked::Id(keys.key(interner, *v)?),
+                        Data::VecId(v) => DataPacked::VecId(
+                            v.iter().flat_map(|id| keys.key(interner, *id)).collect(),
+                        ),
+                        Data::VecStack(v) => DataPacked::VecStack(
+                            v.iter()
+                                .flat_map(|stack| {
+                                    keys.key(interner, stack.item.id)
+                                        .map(|item| (item, stack.amount))
+                                })
+                                .collect(),
+                        ),
+                        Data::Amount(v) => DataPacked::Amount(*v),
+                        Data::Bool(v) => DataPacked::Bool(*v),
+                    };
+
+                    Some((key, value))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl DataMapPacked {
+    /// Reads the data back. Entries and items whose ids are not loaded are given back separately as they were saved, so that they can be saved again.
+    pub fn to_data(
+        &self,
+        keys: &KeyLookup,
+        resource_man: &ResourceManager,
+    ) -> (DataMap, DataMapRaw) {
+        let mut data = DataMap::default();
+        let mut unknown = HashMap::new();
+
+        for (key, value) in &self.0 {
+            let Some(name) = keys.names.get(*key as usize) else {
+                continue;
+            };
+
+            let Some(key) = keys.get(*key) else {
+                if let Some(value) = value.to_raw(&keys.names) {
+                    unknown.insert(name.clone(), value);
+                }
+
+                continue;
+            };
+
+            let (value, rest) = match value {
+                DataPacked::Inventory(v) => {
+                    let (known, rest) = split_known(
+                        v,
+                        |(item, amount)| Some((keys.get(*item)?, *amount)),
+                        |(item, amount)| Some((keys.name(*item)?, *amount)),
+                    );
+
+                    let mut inventory = Inventory::default();
+                    inventory.extend(known);
+
+                    (
+                        Some(Data::Inventory(inventory)),
+                        (!rest.is_empty()).then_some(DataRaw::Inventory(InventoryRaw::from(rest))),
+                    )
+                }
+                DataPacked::Coord(v) => (Some(Data::Coord(*v)), None),
+                DataPacked::VecCoord(v) => (Some(Data::VecCoord(v.clone())), None),
+                DataPacked::Id(v) => match keys.get(*v) {
+                    Some(id) => (Some(Data::Id(id)), None),
+                    None => (None, keys.name(*v).map(DataRaw::Id)),
+                },
+                DataPacked::VecId(v) => {
+                    let (known, rest) = split_known(v, |id| keys.get(*id), |id| keys.name(*id));
+
+                    (
+                        Some(Data::VecId(known)),
+                        (!rest.is_empty()).then_some(DataRaw::VecId(rest)),
+                    )
+                }
+                DataPacked::VecStack(v) => {
+                    let (known, rest) = split_known(
+                        v,
+                        |(item, amount)| {
+                            Some(ItemStack {
+                                item: resource_man.registry.item(keys.get(*item)?).cloned()?,
+                                amount: *amount,
+                            })
+                        },
+                        |(item, amount)| Some((keys.name(*item)?, *amount)),
+                    );
+
+                    (
+                        Some(Data::VecStack(known)),
+                        (!rest.is_empty()).then_some(DataRaw::VecStack(rest)),
+                    )
+                }
+                DataPacked::Amount(v) => (Some(Data::Amount(*v)), None),
+                DataPacked::Bool(v) => (Some(Data::Bool(*v)), None),
+            };
+
+            if let Some(value) = value {
+                data.insert(key, value);
+            }
+
+            if let Some(rest) = rest {
+                unknown.insert(name.clone(), rest);
+            }
+        }
+
+        (data, DataMapRaw(unknown))
+    }
+
+    /// Adds back the entries and items that were given back separately when the data was read. Entries that were set again since keep their new value, though lists of items get the items added back.
+    pub fn merge(&mut self, other: DataMapPacked) {
+        for (key, value) in other.0 {
+            match self.0.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, existing)) => match (existing, value) {
+                    (DataPacked::Inventory(a), DataPacked::Inventory(b))
+                    | (DataPacked::VecStack(a), DataPacked::VecStack(b)) => a.extend(b),
+                    (DataPacked::VecId(a), DataPacked::VecId(b)) => a.extend(b),
+                    _ => {}
+                },
+                None => self.0.push((key, value)),
+            }
+        }
+    }
+}
+
+impl DataMapRaw {
+    pub fn to_packed(&self, keys: &mut KeyTable) -> DataMapPacked {
+        let mut key = |id: &IdRaw| keys.key_raw(&id.to_string());
+
+        DataMapPacked(
+            self.0
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        DataRaw::Inventory(v) => DataPacked::Inventory(
+                            v.iter()
+                                .map(|(item, amount)| (key(item), *amount))
+                                .collect(),
+                        ),
+                        DataRaw::Coord(v) => DataPacked::Coord(*v),
+                        DataRaw::VecCoord(v) => DataPacked::VecCoord(v.clone()),
+                        DataRaw::Id(v) => DataPacked::Id(key(v)),
+                        DataRaw::VecId(v) => DataPacked::VecId(v.iter().map(&mut key).collect()),
+                        DataRaw::VecStack(v) => DataPacked::VecStack(
+                            v.iter()
+                                .map(|(item, amount)| (key(item), *amount))
+                                .collect(),
+                        ),
+                        DataRaw::Amount(v) => DataPacked::Amount(*v),
+                        DataRaw::Bool(v) => DataPacked::Bool(*v),
+                    };
+
+                    (key(&IdRaw::parse(name)), value)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl DataMapPacked {
+    /// Reads the data back without looking the ids up, given the ids in the key table it was saved with.
+    pub fn to_raw(&self, keys: &[String]) -> DataMapRaw {
+        DataMapRaw(
+            self.0
+                .iter()
+                .flat_map(|(key, value)| {
+                    Some((keys.get(*key as usize)?.clone(), value.to_raw(keys)?))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl DataPacked {
+    /// Reads the value back without looking the ids up, given the ids in the key table it was saved with.
+    fn to_raw(&self, keys: &[String]) -> Option<DataRaw> {
+        let id = |key: &Key| keys.get(*key as usize).map(|id| IdRaw::parse(id));
+        let stacks = |v: &[(Key, ItemAmount)]| {
+            v.iter()
+                .flat_map(|(item, amount)| id(item).map(|item| (item, *amount)))
+                .collect::<Vec<_>>()
+        };
+
+        Some(match self {
+            DataPacked::Inventory(v) => DataRaw::Inventory(InventoryRaw::from(stacks(v))),
+            DataPacked::Coord(v) => DataRaw::Coord(*v),
+            DataPacked::VecCoord(v) => DataRaw::VecCoord(v.clone()),
+            DataPacked::Id(v) => DataRaw::Id(id(v)?),
+            DataPacked::VecId(v) => DataRaw::VecId(v.iter().flat_map(id).collect()),
+            DataPacked::VecStack(v) => DataRaw::VecStack(stacks(v)),
+            DataPacked::Amount(v) => DataRaw::Amount(*v),
+            DataPacked::Bool(v) => DataRaw::Bool(*v),
+        })
+    }
+}
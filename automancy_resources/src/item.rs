@@ -10,12 +10,23 @@ use automancy_defs::id::{Id, IdRaw};
 use automancy_defs::log;
 
 use crate::data::item::{item_match, Item};
+use crate::data::stack::ItemAmount;
+use crate::data::DataMapRaw;
 use crate::{load_recursively, ResourceManager, JSON_EXT};
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Debug, Deserialize)]
 struct ItemJson {
     id: IdRaw,
     model: IdRaw,
+    #[serde(default)]
+    category: Option<IdRaw>,
+    #[serde(default)]
+    max_stack: Option<ItemAmount>,
+    #[serde(default)]
+    description: Option<IdRaw>,
+    /// any other properties of the item
+    #[serde(default)]
+    data: DataMapRaw,
 }
 
 impl ResourceManager {
@@ -26,8 +37,21 @@ impl ResourceManager {
 
         let id = item.id.to_id(&mut self.interner);
         let model = item.model.to_id(&mut self.interner);
-
-        self.registry.items.insert(id, Item { id, model });
+        let category = item.category.map(|v| v.to_id(&mut self.interner));
+        let description = item.description.map(|v| v.to_id(&mut self.interner));
+        let data = item.data.intern_to_data(self);
+
+        self.registry.items.insert(
+            id,
+            Item {
+                id,
+                model,
+                category,
+                max_stack: item.max_stack.filter(|v| *v > 0),
+                description,
+            },
+        );
+        self.registry.item_data.insert(id, data);
 
         Ok(())
     }
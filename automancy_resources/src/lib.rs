@@ -1,11 +1,13 @@
 use std::ffi::OsStr;
 use std::fmt;
 use std::fmt::{Debug, Formatter};
+use std::fs::read_dir;
 use std::ops::{Add, Neg, Sub};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 use std::time::SystemTime;
 
+use anyhow::Context;
 pub use chrono;
 use chrono::{DateTime, Local};
 pub use kira;
@@ -21,16 +23,17 @@ use automancy_defs::hashbrown::HashMap;
 use automancy_defs::hexagon_tiles::traits::HexRotate;
 use automancy_defs::id;
 use automancy_defs::id::{id_static, Id, Interner};
+use automancy_defs::log;
 use automancy_defs::rendering::{Animation, Model};
 
-use crate::data::inventory::Inventory;
+use crate::data::inventory::{rhai_count_matching, rhai_take_matching, Inventory};
 use crate::data::item::{rhai_item_match, rhai_item_matches, rhai_item_stack_matches, Item};
 use crate::data::stack::{ItemAmount, ItemStack};
 use crate::data::DataMap;
 use crate::error::ErrorManager;
 use crate::model::IndexRange;
 use crate::registry::{DataIds, ErrorIds, GuiIds, ModelIds, Registry};
-use crate::script::{Instructions, Script};
+use crate::script::{Instructions, Output, Script};
 use crate::tag::Tag;
 use crate::tile::Tile;
 use crate::translate::Translate;
@@ -47,6 +50,7 @@ pub mod shader;
 pub mod tag;
 pub mod tile;
 pub mod translate;
+pub mod worldgen;
 
 static COULD_NOT_GET_FILE_STEM: &str = "could not get file stem";
 
@@ -104,6 +108,8 @@ pub struct ResourceManager {
     pub engine: Engine,
 
     pub registry: Registry,
+    /// the namespaces that were loaded
+    pub namespaces: Vec<String>,
 
     pub translates: Translate,
     pub audio: HashMap<SharedStr, StaticSoundData>,
@@ -177,6 +183,8 @@ impl ResourceManager {
                 .register_fn("take", Inventory::take_with_item)
                 .register_fn("add", Inventory::add)
                 .register_fn("add", Inventory::add_with_item)
+                .register_fn("count_matching", rhai_count_matching)
+                .register_fn("take_matching", rhai_take_matching)
                 .register_indexer_get_set(Inventory::get, Inventory::insert)
                 .register_indexer_get_set(Inventory::get_with_item, Inventory::insert_with_item);
             engine
@@ -184,19 +192,55 @@ impl ResourceManager {
                 .register_iterator::<Vec<Id>>();
             engine
                 .register_type_with_name::<Script>("Script")
-                .register_get("instructions", |v: &mut Script| v.instructions.clone());
+                .register_get("instructions", |v: &mut Script| v.instructions.clone())
+                .register_get("duration", |v: &mut Script| v.duration);
             engine
                 .register_type_with_name::<Instructions>("Instructions")
                 .register_get("inputs", |v: &mut Instructions| match &v.inputs {
                     Some(v) => Dynamic::from_iter(v.clone()),
                     None => Dynamic::UNIT,
                 })
-                .register_get("outputs", |v: &mut Instructions| v.outputs.clone());
+                .register_get("outputs", |v: &mut Instructions| v.outputs.clone())
+                .register_get("byproducts", |v: &mut Instructions| v.byproducts.clone());
+            engine
+                .register_type_with_name::<Output>("Output")
+                .register_iterator::<Vec<Output>>()
+                .register_get("stack", |v: &mut Output| v.stack)
+                .register_get("item", |v: &mut Output| v.stack.item)
+                .register_get("amount", |v: &mut Output| v.stack.amount)
+                .register_get("chance", |v: &mut Output| v.chance)
+                .register_fn("roll", |v: Vec<Output>, random: INT| {
+                    Output::roll(&v, random)
+                });
             engine.register_type_with_name::<Tile>("Tile");
             engine
                 .register_type_with_name::<Item>("Item")
                 .register_iterator::<Vec<Item>>()
                 .register_get("id", |v: &mut Item| v.id)
+                .register_get("category", |v: &mut Item| match v.category {
+                    Some(category) => Dynamic::from(category),
+                    None => Dynamic::UNIT,
+                })
+                .register_get("max_stack", |v: &mut Item| match v.max_stack {
+                    Some(max_stack) => Dynamic::from_int(max_stack),
+                    None => Dynamic::UNIT,
+                })
+                .register_get("description", |v: &mut Item| match v.description {
+                    Some(description) => Dynamic::from(description),
+                    None => Dynamic::UNIT,
+                })
+                .register_get("data", |v: &mut Item| {
+                    RESOURCE_MAN
+                        .read()
+                        .unwrap()
+                        .as_ref()
+                        .unwrap()
+                        .registry
+                        .item_data
+                        .get(&v.id)
+                        .cloned()
+                        .unwrap_or_default()
+                })
                 .register_fn("==", |a: Item, b: Item| a == b)
                 .register_fn("!=", |a: Item, b: Item| a != b);
 
@@ -209,6 +253,43 @@ impl ResourceManager {
                 })
                 .register_get("item", |v: &mut ItemStack| v.item)
                 .register_get("amount", |v: &mut ItemStack| v.amount);
+            engine
+                .register_type_with_name::<Vec<ItemStack>>("VecStack")
+                .register_fn("len", |v: &mut Vec<ItemStack>| v.len() as INT)
+                .register_fn("push", |v: &mut Vec<ItemStack>, stack: ItemStack| {
+                    v.push(stack)
+                })
+                .register_fn("first", |v: &mut Vec<ItemStack>| match v.first() {
+                    Some(stack) => Dynamic::from(*stack),
+                    None => Dynamic::UNIT,
+                })
+                .register_fn(
+                    "take",
+                    |v: &mut Vec<ItemStack>, item: Item, mut amount: ItemAmount| {
+                        for stack in v.iter_mut().filter(|stack| stack.item == item) {
+                            let taken = amount.min(stack.amount);
+
+                            stack.amount -= taken;
+                            amount -= taken;
+                        }
+
+                        v.retain(|stack| stack.amount > 0);
+
+                        amount
+                    },
+                )
+                .register_fn(
+                    "take_first",
+                    |v: &mut Vec<ItemStack>, amount: ItemAmount| {
+                        if let Some(stack) = v.first_mut() {
+                            stack.amount -= amount;
+
+                            if stack.amount <= 0 {
+                                v.remove(0);
+                            }
+                        }
+                    },
+                );
             engine.register_type_with_name::<Tag>("Tag");
         }
 
@@ -287,6 +368,8 @@ impl ResourceManager {
                 scripts: Default::default(),
                 tags: Default::default(),
                 items: Default::default(),
+                item_data: Default::default(),
+                worldgen: Default::default(),
 
                 none,
                 any,
@@ -297,6 +380,8 @@ impl ResourceManager {
                 err_ids,
             },
 
+            namespaces: vec![],
+
             translates: Default::default(),
             audio: Default::default(),
             shaders: Default::default(),
@@ -308,4 +393,41 @@ impl ResourceManager {
             all_models: Default::default(),
         }
     }
+
+    /// Loads all the resources in every namespace in the folder.
+    pub fn load_all(&mut self, path: &Path) -> anyhow::Result<()> {
+        for dir in read_dir(path)
+            .context("The resources folder doesn't exist- this is very wrong")?
+            .flatten()
+            .map(|v| v.path())
+        {
+            let namespace = dir
+                .file_name()
+                .and_then(OsStr::to_str)
+                .ok_or_else(|| LoadResourceError::OsStringError(dir.clone()))?;
+
+            log::info!("loading namespace {namespace}...");
+            self.namespaces.push(namespace.to_string());
+
+            self.load_models(&dir).context("Error loading models")?;
+            self.load_audio(&dir).context("Error loading audio")?;
+            self.load_tiles(&dir).context("Error loading tiles")?;
+            self.load_items(&dir).context("Error loading items")?;
+            self.load_tags(&dir).context("Error loading tags")?;
+            self.load_worldgens(&dir)
+                .context("Error loading world generation")?;
+            self.load_scripts(&dir).context("Error loading scripts")?;
+            self.load_translates(&dir)
+                .context("Error loading translates")?;
+            self.load_shaders(&dir).context("Error loading shaders")?;
+            self.load_functions(&dir)
+                .context("Error loading functions")?;
+
+            log::info!("loaded namespace {namespace}.");
+        }
+
+        self.ordered_items();
+
+        Ok(())
+    }
 }
@@ -3,10 +3,11 @@ use automancy_defs::id::Id;
 use automancy_macros::IdReg;
 
 use crate::data::item::Item;
-use crate::data::Data;
+use crate::data::{Data, DataMap};
 use crate::script::Script;
 use crate::tag::Tag;
 use crate::tile::Tile;
+use crate::worldgen::WorldGen;
 
 /// Represents the resource registry.
 #[derive(Clone)]
@@ -15,6 +16,9 @@ pub struct Registry {
     pub scripts: HashMap<Id, Script>,
     pub tags: HashMap<Id, Tag>,
     pub items: HashMap<Id, Item>,
+    /// the properties of each item, other than what [`Item`] has
+    pub item_data: HashMap<Id, DataMap>,
+    pub worldgen: WorldGen,
 
     pub none: Id,
     pub any: Id,
@@ -42,6 +46,10 @@ impl Registry {
         self.tags.get(&id)
     }
 
+    pub fn item_data(&self, id: Id, data: Id) -> Option<&Data> {
+        self.item_data.get(&id).and_then(|v| v.get(&data))
+    }
+
     pub fn item(&self, id: Id) -> Option<&Item> {
         self.items.get(&id)
     }
@@ -58,11 +66,21 @@ pub struct DataIds {
     pub target: Id,
     pub link: Id,
     pub player_inventory: Id,
+    pub output_blocked: Id,
+    pub queue: Id,
+    pub progress: Id,
+    pub output_buffer: Id,
+    pub byproduct_buffer: Id,
+    pub byproduct_target: Id,
+    pub adjacent_unmet: Id,
 
     pub storage_takeable: Id,
     pub inactive_model: Id,
     pub not_targeted: Id,
+    pub not_placeable: Id,
     pub max_amount: Id,
+    pub transit_ticks: Id,
+    pub transit_capacity: Id,
     pub linked: Id,
     pub linking: Id,
 }
@@ -88,19 +106,33 @@ pub struct GuiIds {
     pub tile_config_script: Id,
     pub tile_config_item: Id,
     pub tile_config_target: Id,
+    pub tile_config_byproduct_target: Id,
     pub error_popup: Id,
     pub debug_menu: Id,
     pub load_map: Id,
     pub delete_map: Id,
+    pub restore_map: Id,
     pub create_map: Id,
     pub invalid_name: Id,
     pub options: Id,
 
     pub lbl_amount: Id,
     pub lbl_link_destination: Id,
+    pub lbl_output_blocked: Id,
+    pub lbl_adjacent_needs: Id,
+    pub lbl_adjacent_forbids: Id,
+    pub lbl_map_seed: Id,
+    pub lbl_map_description: Id,
+    pub lbl_map_created: Id,
+    pub lbl_map_play_time: Id,
+    pub lbl_map_version: Id,
+    pub lbl_map_namespaces: Id,
+    pub lbl_map_missing_namespaces: Id,
+    pub lbl_map_too_new: Id,
     pub lbl_maps_loaded: Id,
     pub lbl_pick_another_name: Id,
     pub lbl_delete_map_confirm: Id,
+    pub lbl_no_backups: Id,
 
     pub btn_confirm: Id,
     pub btn_exit: Id,
@@ -113,6 +145,10 @@ pub struct GuiIds {
     pub btn_unpause: Id,
     pub btn_load: Id,
     pub btn_delete: Id,
+    pub btn_backups: Id,
+    pub btn_restore: Id,
+    pub btn_export: Id,
+    pub btn_import: Id,
     pub btn_new_map: Id,
 
     pub time_fmt: Id,
@@ -123,6 +159,16 @@ pub struct GuiIds {
 pub struct ErrorIds {
     /// This error is displayed when the map cannot be read.
     pub invalid_map_data: Id,
+    /// This error is displayed when the map was saved by a newer version of the game.
+    pub too_new_map: Id,
+    /// This error is displayed when only part of the map can be read.
+    pub recovered_map_data: Id,
     /// This error is displayed when the options cannot be written.
     pub unwritable_options: Id,
+    /// This error is displayed when a map cannot be saved.
+    pub unwritable_map: Id,
+    /// This error is displayed when a map cannot be exported.
+    pub unwritable_map_archive: Id,
+    /// This error is displayed when a map cannot be imported.
+    pub unreadable_map_archive: Id,
 }
@@ -2,11 +2,14 @@ use std::ffi::OsStr;
 use std::fs::read_to_string;
 use std::path::Path;
 
+use rhai::INT;
 use serde::Deserialize;
 
+use automancy_defs::coord::TileCoord;
 use automancy_defs::id::{Id, IdRaw};
 use automancy_defs::log;
 
+use crate::data::item::Item;
 use crate::data::stack::{ItemAmount, ItemStack};
 use crate::{load_recursively, ResourceManager, JSON_EXT};
 
@@ -14,27 +17,149 @@ use crate::{load_recursively, ResourceManager, JSON_EXT};
 pub struct Script {
     pub id: Id,
 
-    pub adjacent: Option<Id>,
+    /// what has to be around the tile for the script to run, all of which must be met
+    pub adjacent: Vec<Adjacency>,
     pub instructions: Instructions,
+    /// how many ticks the script takes to run once, 0 if it runs right away
+    pub duration: INT,
 }
 
+/// A requirement on the neighbors of a tile running a script.
+#[derive(Debug, Clone)]
+pub struct Adjacency {
+    /// the tiles or tags that count, any of them
+    pub any_of: Vec<Id>,
+    /// the only neighbor that is looked at, if set
+    pub direction: Option<TileCoord>,
+    /// how many neighbors have to count
+    pub count: usize,
+    /// if set, fewer than `count` neighbors have to count instead
+    pub negated: bool,
+}
+
+impl Adjacency {
+    /// Checks the requirement, given how many neighbors count.
+    pub fn fulfilled(&self, matching: usize) -> bool {
+        (matching >= self.count) != self.negated
+    }
+}
+
+/// The chance of an output that always comes out, in per-mille.
+pub const CERTAIN: INT = 1000;
+
 #[derive(Debug, Clone)]
 pub struct Instructions {
     pub inputs: Option<Vec<ItemStack>>,
-    pub outputs: Vec<ItemStack>,
+    pub outputs: Vec<Output>,
+    /// outputs that go to the second target
+    pub byproducts: Vec<Output>,
+}
+
+/// An item stack a script makes, and its chance of coming out each run.
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    pub stack: ItemStack,
+    /// in per-mille
+    pub chance: INT,
+}
+
+impl Output {
+    /// Picks the outputs that come out this run. The same random seed always picks the same outputs.
+    pub fn roll(outputs: &[Output], random: INT) -> Vec<ItemStack> {
+        let mut seed = random as u64;
+
+        outputs
+            .iter()
+            .filter(|output| {
+                if output.chance >= CERTAIN {
+                    return true;
+                }
+
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+
+                ((seed >> 33) % CERTAIN as u64) < output.chance.max(0) as u64
+            })
+            .map(|output| output.stack)
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ScriptJson {
     pub id: IdRaw,
-    pub adjacent: Option<IdRaw>,
+    #[serde(default)]
+    pub adjacent: Option<AdjacentJson>,
     pub instructions: InstructionsJson,
+    #[serde(default)]
+    pub duration: INT,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct InstructionsJson {
     pub inputs: Option<Vec<(IdRaw, ItemAmount)>>,
-    pub output: Vec<(IdRaw, ItemAmount)>,
+    pub output: Vec<OutputJson>,
+    #[serde(default)]
+    pub byproducts: Vec<OutputJson>,
+}
+
+/// An output, either `[id, amount]` or `[id, amount, chance in per-mille]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum OutputJson {
+    Certain(IdRaw, ItemAmount),
+    Chance(IdRaw, ItemAmount, INT),
+}
+
+/// Either a single tile or tag that has to be next to the tile, or a list of requirements.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum AdjacentJson {
+    Single(IdRaw),
+    Many(Vec<AdjacencyJson>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdjacencyJson {
+    pub any_of: Vec<IdRaw>,
+    #[serde(default)]
+    pub direction: Option<DirectionJson>,
+    #[serde(default = "one")]
+    pub count: usize,
+    #[serde(default)]
+    pub not: bool,
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectionJson {
+    TopRight,
+    Right,
+    BottomRight,
+    BottomLeft,
+    Left,
+    TopLeft,
+    /// the terrain under the tile itself
+    Below,
+}
+
+impl From<DirectionJson> for TileCoord {
+    fn from(value: DirectionJson) -> Self {
+        match value {
+            DirectionJson::TopRight => TileCoord::TOP_RIGHT,
+            DirectionJson::Right => TileCoord::RIGHT,
+            DirectionJson::BottomRight => TileCoord::BOTTOM_RIGHT,
+            DirectionJson::BottomLeft => TileCoord::BOTTOM_LEFT,
+            DirectionJson::Left => TileCoord::LEFT,
+            DirectionJson::TopLeft => TileCoord::TOP_LEFT,
+            DirectionJson::Below => TileCoord::ZERO,
+        }
+    }
 }
 
 impl ResourceManager {
@@ -49,32 +174,44 @@ impl ResourceManager {
             inputs: script.instructions.inputs.map(|v| {
                 v.into_iter()
                     .flat_map(|(id, amount)| {
-                        self.registry
-                            .item(id.to_id(&mut self.interner))
-                            .cloned()
-                            .map(|item| ItemStack { item, amount })
+                        let id = id.to_id(&mut self.interner);
+
+                        self.input_item(id).map(|item| ItemStack { item, amount })
                     })
                     .collect()
             }),
-            outputs: script
-                .instructions
-                .output
+            outputs: self.load_outputs(script.instructions.output),
+            byproducts: self.load_outputs(script.instructions.byproducts),
+        };
+
+        let adjacent = match script.adjacent {
+            None => vec![],
+            Some(AdjacentJson::Single(id)) => vec![Adjacency {
+                any_of: vec![id.to_id(&mut self.interner)],
+                direction: None,
+                count: 1,
+                negated: false,
+            }],
+            Some(AdjacentJson::Many(adjacent)) => adjacent
                 .into_iter()
-                .flat_map(|(id, amount)| {
-                    self.registry
-                        .item(id.to_id(&mut self.interner))
-                        .cloned()
-                        .map(|item| ItemStack { item, amount })
+                .map(|adjacency| Adjacency {
+                    any_of: adjacency
+                        .any_of
+                        .into_iter()
+                        .map(|id| id.to_id(&mut self.interner))
+                        .collect(),
+                    direction: adjacency.direction.map(TileCoord::from),
+                    count: adjacency.count,
+                    negated: adjacency.not,
                 })
                 .collect(),
         };
 
-        let adjacent = script.adjacent.map(|id| id.to_id(&mut self.interner));
-
         let script = Script {
             id,
             instructions,
             adjacent,
+            duration: script.duration.max(0),
         };
 
         self.registry.scripts.insert(id, script);
@@ -82,6 +219,50 @@ impl ResourceManager {
         Ok(())
     }
 
+    /// Gets the item an input accepts. A tag becomes an item with the tag's id, which matches any item of the tag.
+    fn input_item(&self, id: Id) -> Option<Item> {
+        if let Some(item) = self.registry.item(id) {
+            Some(*item)
+        } else if self.registry.tags.contains_key(&id) {
+            Some(Item {
+                id,
+                model: self.registry.model_ids.items_missing,
+                category: None,
+                max_stack: None,
+                description: None,
+            })
+        } else {
+            log::warn!(
+                "script input {} is neither an item nor a tag",
+                self.interner.resolve(id).unwrap_or_default()
+            );
+
+            None
+        }
+    }
+
+    fn load_outputs(&mut self, outputs: Vec<OutputJson>) -> Vec<Output> {
+        outputs
+            .into_iter()
+            .flat_map(|output| {
+                let (id, amount, chance) = match output {
+                    OutputJson::Certain(id, amount) => (id, amount, CERTAIN),
+                    OutputJson::Chance(id, amount, chance) => {
+                        (id, amount, chance.clamp(0, CERTAIN))
+                    }
+                };
+
+                self.registry
+                    .item(id.to_id(&mut self.interner))
+                    .cloned()
+                    .map(|item| Output {
+                        stack: ItemStack { item, amount },
+                        chance,
+                    })
+            })
+            .collect()
+    }
+
     pub fn load_scripts(&mut self, dir: &Path) -> anyhow::Result<()> {
         let scripts = dir.join("scripts");
 
@@ -20,17 +20,66 @@ pub struct TagJson {
 #[derive(Debug, Clone)]
 pub struct Tag {
     pub id: Id,
+    /// the items and tags in the tag. The items of a tag in it are in it too.
     pub entries: HashSet<Id>,
 }
 
 impl Tag {
     pub fn of(&self, registry: &Registry, id: Id) -> bool {
         if self.id == registry.any {
-            true
-        } else {
-            self.entries.contains(&id)
+            return true;
         }
+
+        self.entries.iter().any(|entry| {
+            *entry == id
+                || registry
+                    .tag(*entry)
+                    .map(|tag| tag.of(registry, id))
+                    .unwrap_or(false)
+        })
+    }
+}
+
+/// Finds a tag that includes itself through other tags, and returns the path from it back to itself.
+fn find_cycle(registry: &Registry) -> Option<Vec<Id>> {
+    fn visit(
+        registry: &Registry,
+        id: Id,
+        path: &mut Vec<Id>,
+        done: &mut HashSet<Id>,
+    ) -> Option<Vec<Id>> {
+        if let Some(start) = path.iter().position(|v| *v == id) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(id);
+
+            return Some(cycle);
+        }
+
+        if done.contains(&id) {
+            return None;
+        }
+
+        let tag = registry.tag(id)?;
+
+        path.push(id);
+        for entry in &tag.entries {
+            if let Some(cycle) = visit(registry, *entry, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+
+        done.insert(id);
+
+        None
     }
+
+    let mut done = HashSet::new();
+
+    registry
+        .tags
+        .keys()
+        .find_map(|id| visit(registry, *id, &mut vec![], &mut done))
 }
 
 impl ResourceManager {
@@ -41,16 +90,22 @@ impl ResourceManager {
 
         let id = tag.id.to_id(&mut self.interner);
 
-        let tag = Tag {
-            id,
-            entries: tag
-                .entries
-                .into_iter()
-                .map(|id| id.to_id(&mut self.interner))
-                .collect(),
-        };
+        let entries = tag
+            .entries
+            .into_iter()
+            .map(|id| id.to_id(&mut self.interner))
+            .collect::<Vec<_>>();
 
-        self.registry.tags.insert(id, tag);
+        // a tag declared again, by another namespace or file, gets the new entries added to it
+        self.registry
+            .tags
+            .entry(id)
+            .or_insert_with(|| Tag {
+                id,
+                entries: Default::default(),
+            })
+            .entries
+            .extend(entries);
 
         Ok(())
     }
@@ -62,6 +117,22 @@ impl ResourceManager {
             self.load_tag(&file)?;
         }
 
+        // the tags in a cycle are dropped, so the rest still load
+        while let Some(cycle) = find_cycle(&self.registry) {
+            log::error!(
+                "tags include each other in a cycle, dropping them: {}",
+                cycle
+                    .iter()
+                    .flat_map(|id| self.interner.resolve(*id))
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            );
+
+            for id in cycle {
+                self.registry.tags.remove(&id);
+            }
+        }
+
         Ok(())
     }
 }
@@ -5,6 +5,7 @@ use std::path::Path;
 use serde::Deserialize;
 use serde_json;
 
+use automancy_defs::coord::TileCoord;
 use automancy_defs::id::{Id, IdRaw};
 use automancy_defs::log;
 
@@ -18,6 +19,8 @@ pub struct TileJson {
     pub models: Vec<IdRaw>,
     #[serde(default)]
     pub data: DataMapRaw,
+    #[serde(default)]
+    pub footprint: Vec<TileCoord>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +28,8 @@ pub struct Tile {
     pub models: Vec<Id>,
     pub function: Option<Id>,
     pub data: DataMap,
+    /// the other positions the tile takes up, relative to its own
+    pub footprint: Vec<TileCoord>,
 }
 
 impl ResourceManager {
@@ -51,6 +56,11 @@ impl ResourceManager {
                 function,
                 models,
                 data,
+                footprint: tile
+                    .footprint
+                    .into_iter()
+                    .filter(|offset| *offset != TileCoord::ZERO)
+                    .collect(),
             },
         );
 
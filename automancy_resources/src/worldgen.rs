@@ -0,0 +1,143 @@
+use std::ffi::OsStr;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use automancy_defs::coord::{ChunkCoord, TileCoord};
+use automancy_defs::id::{Id, IdRaw};
+use automancy_defs::log;
+use automancy_defs::math::{hex_to_pixel, Double};
+
+use crate::{load_recursively, ResourceManager, JSON_EXT};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DepositJson {
+    pub tile: IdRaw,
+    pub scale: Double,
+    pub threshold: Double,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorldGenJson {
+    #[serde(default)]
+    pub deposits: Vec<DepositJson>,
+}
+
+/// A kind of tile the world generator scatters in patches.
+#[derive(Debug, Clone)]
+pub struct Deposit {
+    pub tile: Id,
+    /// roughly how many tiles apart the patches are
+    pub scale: Double,
+    /// from 0 to 1. The higher it is, the smaller and rarer the patches are
+    pub threshold: Double,
+}
+
+/// The parameters of the world generator.
+#[derive(Debug, Clone, Default)]
+pub struct WorldGen {
+    /// the deposits, the first ones taking priority where they overlap
+    pub deposits: Vec<Deposit>,
+}
+
+impl WorldGen {
+    /// Gets the tiles to place in the chunk, for the given seed. The same seed always gives the same tiles.
+    pub fn generate(&self, seed: u64, chunk: ChunkCoord) -> Vec<(TileCoord, Id)> {
+        chunk
+            .iter()
+            .flat_map(|coord| {
+                let p = hex_to_pixel(coord.into());
+
+                self.deposits
+                    .iter()
+                    .enumerate()
+                    .find(|(index, deposit)| {
+                        noise(
+                            seed.wrapping_add(*index as u64),
+                            p.x / deposit.scale,
+                            p.y / deposit.scale,
+                        ) > deposit.threshold
+                    })
+                    .map(|(_, deposit)| (coord, deposit.tile))
+            })
+            .collect()
+    }
+}
+
+/// Turns what the player typed into a seed. Numbers are used as is, and nothing gives a random seed.
+pub fn seed_from_text(text: &str) -> u64 {
+    let text = text.trim();
+
+    if text.is_empty() {
+        return SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|v| v.as_nanos() as u64)
+            .unwrap_or_default();
+    }
+
+    text.parse().unwrap_or_else(|_| {
+        // FNV-1a, so that the same text gives the same seed everywhere
+        text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    })
+}
+
+/// Gets a random value from 0 to 1 for the lattice point.
+fn lattice(seed: u64, x: i64, y: i64) -> Double {
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9e3779b97f4a7c15)
+        ^ (y as u64).wrapping_mul(0xc2b2ae3d27d4eb4f);
+
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^= h >> 33;
+
+    (h >> 11) as Double / (1u64 << 53) as Double
+}
+
+/// Smooth value noise from 0 to 1, changing over a distance of about 1.
+fn noise(seed: u64, x: Double, y: Double) -> Double {
+    let (x0, y0) = (x.floor(), y.floor());
+    let smooth = |t: Double| t * t * (3.0 - 2.0 * t);
+    let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let top = lattice(seed, x0, y0) * (1.0 - tx) + lattice(seed, x0 + 1, y0) * tx;
+    let bottom = lattice(seed, x0, y0 + 1) * (1.0 - tx) + lattice(seed, x0 + 1, y0 + 1) * tx;
+
+    top * (1.0 - ty) + bottom * ty
+}
+
+impl ResourceManager {
+    fn load_worldgen(&mut self, file: &Path) -> anyhow::Result<()> {
+        log::info!("loading world generation at: {file:?}");
+
+        let worldgen: WorldGenJson = serde_json::from_str(&read_to_string(file)?)?;
+
+        // every namespace can add its own deposits
+        for deposit in worldgen.deposits {
+            self.registry.worldgen.deposits.push(Deposit {
+                tile: deposit.tile.to_id(&mut self.interner),
+                scale: deposit.scale.max(1.0),
+                threshold: deposit.threshold,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn load_worldgens(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let worldgen = dir.join("worldgen");
+
+        for file in load_recursively(&worldgen, OsStr::new(JSON_EXT)) {
+            self.load_worldgen(&file)?;
+        }
+
+        Ok(())
+    }
+}
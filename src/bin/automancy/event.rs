@@ -8,6 +8,8 @@ use egui::Rect;
 use fuse_rust::Fuse;
 use futures::channel::mpsc;
 use futures::executor::block_on;
+use ractor::concurrency::{oneshot, OneshotReceiver};
+use tokio::sync::oneshot::error::TryRecvError;
 use wgpu::SurfaceError;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::ControlFlow;
@@ -15,6 +17,7 @@ use winit::event_loop::ControlFlow;
 use automancy::game::{GameMsg, PlaceTileResponse};
 use automancy::input;
 use automancy::input::KeyActions;
+use automancy::map::MapInfo;
 use automancy::tile_entity::{TileEntityMsg, TileModifier};
 use automancy_defs::cgmath::{point2, vec3, EuclideanSpace};
 use automancy_defs::colors::ColorAdj;
@@ -43,10 +46,14 @@ pub struct EventLoopStorage {
     pub filter_input: String,
     /// input for the map name
     pub map_name_input: String,
+    pub map_seed_input: String,
+    pub map_description_input: String,
     /// storage which map to rename
     pub map_name_renaming: Option<String>,
     /// input for map renaming
     pub map_name_renaming_input: String,
+    /// the backups of the map being restored
+    pub map_backups: Vec<(usize, MapInfo)>,
     /// the tile states of the selected tiles.
     pub selected_tile_modifiers: HashMap<Id, TileModifier>,
     /// the currently selected tile.
@@ -67,6 +74,10 @@ pub struct EventLoopStorage {
     pub selected_tiles: HashSet<TileCoord>,
     /// the stored initial cursor position, for moving tiles
     pub initial_cursor_position: Option<TileCoord>,
+    /// the tiles the game publishes the data of
+    pub watched_tiles: Vec<TileCoord>,
+    /// the replies to the placements that the game has not answered yet
+    pub pending_placements: Vec<OneshotReceiver<PlaceTileResponse>>,
 
     pub take_item_animations: HashMap<Item, VecDeque<(Instant, Rect)>>,
 
@@ -82,8 +93,11 @@ impl Default for EventLoopStorage {
             fuse: Default::default(),
             filter_input: "".to_string(),
             map_name_input: "".to_string(),
+            map_seed_input: "".to_string(),
+            map_description_input: "".to_string(),
             map_name_renaming: None,
             map_name_renaming_input: "".to_string(),
+            map_backups: Default::default(),
             selected_tile_modifiers: Default::default(),
             selected_id: None,
             already_placed_at: None,
@@ -94,6 +108,8 @@ impl Default for EventLoopStorage {
             elapsed: Default::default(),
             selected_tiles: Default::default(),
             initial_cursor_position: None,
+            watched_tiles: Default::default(),
+            pending_placements: Default::default(),
             take_item_animations: Default::default(),
 
             prev_gui_state: None,
@@ -139,7 +155,7 @@ pub fn shutdown_graceful(
     setup.game.send_message(GameMsg::StopTicking)?;
 
     block_on(setup.game.call(
-        |reply| GameMsg::SaveMap(setup.resource_man.clone(), reply),
+        |reply| GameMsg::SaveMap(setup.resource_man.clone(), Some(reply)),
         None,
     ))
     .unwrap();
@@ -231,7 +247,10 @@ pub fn on_event(
             if setup.camera_chunk_coord != camera_chunk_coord {
                 setup.camera_chunk_coord = camera_chunk_coord;
 
-                // camera chunk coord update logic ...
+                setup
+                    .game
+                    .send_message(GameMsg::GenerateAround(camera_chunk_coord))
+                    .unwrap();
             }
         }
 
@@ -239,11 +258,9 @@ pub fn on_event(
             // one by one
             if loop_store.selected_id.take().is_none() && loop_store.linking_tile.take().is_none() {
                 if loop_store.switch_gui_state_when(&|s| s == GuiState::Ingame, GuiState::Paused) {
-                    block_on(setup.game.call(
-                        |reply| GameMsg::SaveMap(setup.resource_man.clone(), reply),
-                        None,
-                    ))?
-                    .unwrap();
+                    setup
+                        .game
+                        .send_message(GameMsg::SaveMap(setup.resource_man.clone(), None))?;
                 } else {
                     loop_store.switch_gui_state_when(&|s| s == GuiState::Paused, GuiState::Ingame);
                 }
@@ -254,40 +271,49 @@ pub fn on_event(
             }
         }
 
+        loop_store
+            .pending_placements
+            .retain_mut(|response| match response.try_recv() {
+                Ok(PlaceTileResponse::Placed) => {
+                    setup
+                        .audio_man
+                        .play(resource_man.audio["tile_placement"].clone())
+                        .unwrap();
+
+                    false
+                }
+                Ok(PlaceTileResponse::Removed) => {
+                    setup
+                        .audio_man
+                        .play(resource_man.audio["tile_removal"].clone())
+                        .unwrap();
+
+                    false
+                }
+                Ok(PlaceTileResponse::Ignored) => false,
+                Err(TryRecvError::Empty) => true,
+                Err(TryRecvError::Closed) => false,
+            });
+
         if setup.input_handler.main_pressed
             || (setup.input_handler.shift_held && setup.input_handler.main_held)
         {
             if let Some(id) = loop_store.selected_id {
                 if loop_store.already_placed_at != Some(setup.camera.pointing_at) {
-                    let response = block_on(setup.game.call(
-                        |reply| GameMsg::PlaceTile {
-                            coord: setup.camera.pointing_at,
-                            id,
-                            tile_modifier:
-                                *loop_store.selected_tile_modifiers.get(&id).unwrap_or(&0),
-                            record: true,
-                            reply: Some(reply),
-                        },
-                        None,
-                    ))?
-                    .unwrap();
+                    let tile_modifier = *loop_store.selected_tile_modifiers.get(&id).unwrap_or(&0);
 
-                    match response {
-                        PlaceTileResponse::Placed => {
-                            setup
-                                .audio_man
-                                .play(resource_man.audio["tile_placement"].clone())
-                                .unwrap();
-                        }
-                        PlaceTileResponse::Removed => {
-                            setup
-                                .audio_man
-                                .play(resource_man.audio["tile_removal"].clone())
-                                .unwrap();
-                        }
-                        _ => {}
-                    }
+                    // the game answers later, so that the frame does not wait on it
+                    let (reply, response) = oneshot();
+
+                    setup.game.send_message(GameMsg::PlaceTile {
+                        coord: setup.camera.pointing_at,
+                        id,
+                        tile_modifier,
+                        record: true,
+                        reply: Some(reply.into()),
+                    })?;
 
+                    loop_store.pending_placements.push(response);
                     loop_store.already_placed_at = Some(setup.camera.pointing_at)
                 }
             }
@@ -295,19 +321,17 @@ pub fn on_event(
 
         if !setup.input_handler.control_held && setup.input_handler.alternate_pressed {
             if let Some(linking_tile) = loop_store.linking_tile {
-                let tile = block_on(setup.game.call(
-                    |reply| GameMsg::GetTile(setup.camera.pointing_at, reply),
-                    None,
-                ))?
-                .unwrap();
-
-                let tile_entity = block_on(setup.game.call(
-                    |reply| GameMsg::GetTileEntity(setup.camera.pointing_at, reply),
-                    None,
-                ))?
-                .unwrap();
-
-                if let Some((linked, tile_entity)) = tile
+                let (tile, data) = {
+                    let game_state = setup.game_state.borrow();
+                    let coord = game_state.owner(setup.camera.pointing_at);
+
+                    (
+                        game_state.tiles.get(&coord).cloned(),
+                        game_state.tile_data.get(&coord).cloned(),
+                    )
+                };
+
+                if let Some((linked, data)) = tile
                     .and_then(|(id, _)| {
                         resource_man
                             .registry
@@ -315,31 +339,26 @@ pub fn on_event(
                             .and_then(Data::as_bool)
                             .cloned()
                     })
-                    .zip(tile_entity)
+                    .zip(data)
                 {
                     if linked {
-                        let old = block_on(tile_entity.call(
-                            |reply| {
-                                TileEntityMsg::GetDataValue(
-                                    resource_man.registry.data_ids.link,
-                                    reply,
-                                )
-                            },
-                            None,
-                        ))?
-                        .unwrap();
+                        let old = data.get(&resource_man.registry.data_ids.link);
 
                         if old.is_some() {
-                            tile_entity.send_message(TileEntityMsg::RemoveData(
-                                resource_man.registry.data_ids.link,
+                            setup.game.send_message(GameMsg::ForwardMsgToTile(
+                                setup.camera.pointing_at,
+                                TileEntityMsg::RemoveData(resource_man.registry.data_ids.link),
                             ))?;
 
                             setup.audio_man.play(resource_man.audio["click"].clone())?;
                             // TODO click2
                         } else {
-                            tile_entity.send_message(TileEntityMsg::SetDataValue(
-                                resource_man.registry.data_ids.link,
-                                Data::Coord(linking_tile),
+                            setup.game.send_message(GameMsg::ForwardMsgToTile(
+                                setup.camera.pointing_at,
+                                TileEntityMsg::SetDataValue(
+                                    resource_man.registry.data_ids.link,
+                                    Data::Coord(linking_tile),
+                                ),
                             ))?;
 
                             setup.audio_man.play(resource_man.audio["click"].clone())?;
@@ -426,6 +445,18 @@ pub fn on_event(
             loop_store.elapsed.as_secs_f64(),
         );
 
+        let watched_tiles = [Some(setup.camera.pointing_at), loop_store.config_open]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        if watched_tiles != loop_store.watched_tiles {
+            setup
+                .game
+                .send_message(GameMsg::WatchTiles(watched_tiles.clone()))?;
+            loop_store.watched_tiles = watched_tiles;
+        }
+
         let (width, height) = window::window_size_float(&renderer.gpu.window);
         let aspect = width / height;
         let camera_pos_float = setup.camera.get_pos().cast().unwrap();
@@ -554,6 +585,9 @@ pub fn on_event(
             PopupState::MapDeleteConfirmation(map_name) => {
                 popup::map_delete_popup(setup, gui, loop_store, &map_name);
             }
+            PopupState::MapRestore(map_name) => {
+                popup::map_restore_popup(setup, gui, loop_store, &map_name);
+            }
             PopupState::InvalidName => {
                 popup::invalid_name_popup(setup, gui, loop_store);
             }
@@ -1,7 +1,4 @@
 use egui::{Context, Window};
-use futures::executor::block_on;
-
-use automancy::game::GameMsg;
 
 use crate::event::EventLoopStorage;
 use crate::gui::default_frame;
@@ -21,11 +18,11 @@ pub fn debugger(setup: &GameSetup, loop_store: &mut EventLoopStorage, context: &
     let audio = resource_man.audio.len();
     let meshes = resource_man.all_models.len();
 
-    let (info, map_name) = block_on(setup.game.call(GameMsg::GetMapInfo, None))
-        .unwrap()
-        .unwrap();
+    let (tile_count, map_name) = {
+        let game_state = setup.game_state.borrow();
 
-    let tile_count = info.tile_count;
+        (game_state.tiles.len(), game_state.map_name.clone())
+    };
 
     Window::new(
         setup.resource_man.translates.gui[&resource_man.registry.gui_ids.debug_menu].as_str(),
@@ -1,17 +1,29 @@
-use egui::{vec2, Align2, Context, Window};
-use futures::executor::block_on;
+use egui::{vec2, Align2, Context, ProgressBar, Window};
 
-use automancy::game::GameMsg;
-use automancy::tile_entity::TileEntityMsg;
 use automancy_defs::colors;
+use automancy_defs::coord::TileCoord;
 use automancy_resources::data::stack::ItemStack;
 use automancy_resources::data::Data;
+use automancy_resources::format;
 
 use crate::gui::default_frame;
 use crate::gui::item::{draw_item, SMALL_ITEM_ICON_SIZE};
 use crate::renderer::GuiInstances;
 use crate::setup::GameSetup;
 
+fn direction_arrow(direction: TileCoord) -> &'static str {
+    match direction {
+        TileCoord::TOP_RIGHT => "↗",
+        TileCoord::RIGHT => "➡",
+        TileCoord::BOTTOM_RIGHT => "↘",
+        TileCoord::BOTTOM_LEFT => "↙",
+        TileCoord::LEFT => "⬅",
+        TileCoord::TOP_LEFT => "↖",
+        TileCoord::ZERO => "⬇",
+        _ => "",
+    }
+}
+
 /// Draws the info GUI.
 pub fn info(setup: &GameSetup, item_instances: &mut GuiInstances, context: &Context) {
     Window::new(
@@ -24,26 +36,93 @@ pub fn info(setup: &GameSetup, item_instances: &mut GuiInstances, context: &Cont
     .show(context, |ui| {
         ui.colored_label(colors::DARK_GRAY, setup.camera.pointing_at.to_string());
 
-        let tile_entity = block_on(setup.game.call(
-            |reply| GameMsg::GetTileEntity(setup.camera.pointing_at, reply),
-            None,
-        ))
-        .unwrap()
-        .unwrap();
+        let (tile, data) = {
+            let game_state = setup.game_state.borrow();
+            let coord = game_state.owner(setup.camera.pointing_at);
 
-        let tile = block_on(setup.game.call(
-            |reply| GameMsg::GetTile(setup.camera.pointing_at, reply),
-            None,
-        ))
-        .unwrap()
-        .unwrap();
+            (
+                game_state.tiles.get(&coord).cloned(),
+                game_state.tile_data.get(&coord).cloned(),
+            )
+        };
 
-        if let Some((tile_entity, (id, _))) = tile_entity.zip(tile) {
+        if let Some(((id, _), data)) = tile.zip(data) {
             ui.label(setup.resource_man.tile_name(&id));
 
-            let data = block_on(tile_entity.call(TileEntityMsg::GetData, None))
-                .unwrap()
-                .unwrap();
+            if data
+                .get(&setup.resource_man.registry.data_ids.output_blocked)
+                .and_then(Data::as_bool)
+                .cloned()
+                .unwrap_or(false)
+            {
+                ui.colored_label(
+                    colors::RED,
+                    setup.resource_man.translates.gui
+                        [&setup.resource_man.registry.gui_ids.lbl_output_blocked]
+                        .as_str(),
+                );
+            }
+
+            if let Some(adjacency) = data
+                .get(&setup.resource_man.registry.data_ids.adjacent_unmet)
+                .and_then(Data::as_amount)
+                .zip(
+                    data.get(&setup.resource_man.registry.data_ids.script)
+                        .and_then(Data::as_id)
+                        .and_then(|script| setup.resource_man.registry.script(*script)),
+                )
+                .and_then(|(index, script)| script.adjacent.get(*index as usize))
+            {
+                let mut names = adjacency
+                    .any_of
+                    .iter()
+                    .map(|id| {
+                        if setup.resource_man.registry.tile(*id).is_some() {
+                            setup.resource_man.tile_name(id)
+                        } else {
+                            setup.resource_man.item_name(id)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                if let Some(direction) = adjacency.direction {
+                    names = format!("{names} ({})", direction_arrow(direction));
+                }
+
+                let label = if adjacency.negated {
+                    setup.resource_man.registry.gui_ids.lbl_adjacent_forbids
+                } else {
+                    setup.resource_man.registry.gui_ids.lbl_adjacent_needs
+                };
+
+                ui.colored_label(
+                    colors::RED,
+                    format(
+                        setup.resource_man.translates.gui[&label].as_str(),
+                        &[&adjacency.count.to_string(), &names],
+                    ),
+                );
+            }
+
+            if let Some(duration) = data
+                .get(&setup.resource_man.registry.data_ids.script)
+                .and_then(Data::as_id)
+                .and_then(|script| setup.resource_man.registry.script(*script))
+                .map(|script| script.duration)
+                .filter(|duration| *duration > 0)
+            {
+                let progress = data
+                    .get(&setup.resource_man.registry.data_ids.progress)
+                    .and_then(Data::as_amount)
+                    .cloned()
+                    .unwrap_or(0);
+
+                ui.add(
+                    ProgressBar::new(progress.clamp(0, duration) as f32 / duration as f32)
+                        .show_percentage(),
+                );
+            }
 
             if let Some(inventory) = data
                 .get(&setup.resource_man.registry.data_ids.buffer)
@@ -55,7 +55,16 @@ pub fn draw_item(
 
         paint_item(resource_man, item_instances, stack.item, rect);
 
-        (rect, icon_response.union(label_response))
+        let response = icon_response.union(label_response);
+
+        if let Some(description) = stack.item.description {
+            (
+                rect,
+                response.on_hover_text(resource_man.item_name(&description)),
+            )
+        } else {
+            (rect, response)
+        }
     })
     .inner
 }
@@ -1,16 +1,17 @@
 use std::fs;
+use std::time::Duration;
 
 use egui::{
     vec2, Align, Align2, Button, Context, RichText, ScrollArea, TextEdit, TextStyle, Window,
 };
-use futures::executor::block_on;
+use native_dialog::FileDialog;
 use winit::event_loop::ControlFlow;
 
 use automancy::game::GameMsg;
-use automancy::map::{Map, MAIN_MENU};
+use automancy::map::{Map, ARCHIVE_EXT, MAIN_MENU};
 use automancy::VERSION;
 use automancy_defs::gui::HyperlinkWidget;
-use automancy_defs::log;
+use automancy_defs::{colors, log};
 use automancy_resources::{format, format_time};
 
 use crate::event::{shutdown_graceful, EventLoopStorage};
@@ -161,11 +162,11 @@ pub fn pause_menu(setup: &GameSetup, context: &Context, loop_store: &mut EventLo
                         )
                         .clicked()
                     {
-                        block_on(setup.game.call(
-                            |reply| GameMsg::SaveMap(setup.resource_man.clone(), reply),
-                            None,
-                        ))
-                        .unwrap();
+                        // the game handles its messages in order, so the map is saved before the menu is loaded
+                        setup
+                            .game
+                            .send_message(GameMsg::SaveMap(setup.resource_man.clone(), None))
+                            .unwrap();
                         setup
                             .game
                             .send_message(GameMsg::LoadMap(
@@ -181,6 +182,60 @@ pub fn pause_menu(setup: &GameSetup, context: &Context, loop_store: &mut EventLo
         });
 }
 
+/// Formats how long a map was played, in hours and minutes.
+fn format_play_time(play_time: Duration) -> String {
+    let minutes = play_time.as_secs() / 60;
+
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Asks the player where to export the map to, and exports it there.
+fn export_map(setup: &GameSetup, map_name: &str) {
+    let Ok(Some(path)) = FileDialog::new()
+        .set_filename(&format!("{map_name}{ARCHIVE_EXT}"))
+        .add_filter("Map", &["zst"])
+        .show_save_single_file()
+    else {
+        return;
+    };
+
+    if let Err(e) = Map::export(map_name, &path) {
+        log::error!("Could not export map {map_name}: {e:?}");
+
+        setup.resource_man.error_man.push(
+            (
+                setup.resource_man.registry.err_ids.unwritable_map_archive,
+                vec![map_name.to_string(), e.to_string()],
+            ),
+            &setup.resource_man,
+        );
+    }
+}
+
+/// Asks the player for an exported map, and imports it.
+fn import_map(setup: &mut GameSetup) {
+    let Ok(Some(path)) = FileDialog::new()
+        .add_filter("Map", &["zst"])
+        .show_open_single_file()
+    else {
+        return;
+    };
+
+    if let Err(e) = Map::import(&path) {
+        log::error!("Could not import {path:?}: {e:?}");
+
+        setup.resource_man.error_man.push(
+            (
+                setup.resource_man.registry.err_ids.unreadable_map_archive,
+                vec![path.display().to_string(), e.to_string()],
+            ),
+            &setup.resource_man,
+        );
+    }
+
+    setup.refresh_maps();
+}
+
 /// Draws the map loading menu.
 pub fn map_menu(setup: &mut GameSetup, context: &Context, loop_store: &mut EventLoopStorage) {
     Window::new(
@@ -240,6 +295,84 @@ pub fn map_menu(setup: &mut GameSetup, context: &Context, loop_store: &mut Event
                         }
                     });
 
+                    if !map_info.description.is_empty() {
+                        ui.label(map_info.description.as_str());
+                    }
+
+                    if let Some(created) = map_info.created {
+                        ui.label(format(
+                            setup.resource_man.translates.gui
+                                [&setup.resource_man.registry.gui_ids.lbl_map_created]
+                                .as_str(),
+                            &[format_time(
+                                created,
+                                setup.resource_man.translates.gui
+                                    [&setup.resource_man.registry.gui_ids.time_fmt]
+                                    .as_str(),
+                            )
+                            .as_str()],
+                        ));
+                    }
+
+                    ui.label(format(
+                        setup.resource_man.translates.gui
+                            [&setup.resource_man.registry.gui_ids.lbl_map_play_time]
+                            .as_str(),
+                        &[
+                            format_play_time(map_info.play_time).as_str(),
+                            map_info.tick_count.to_string().as_str(),
+                        ],
+                    ));
+
+                    if let Some(version) = &map_info.version {
+                        let label = format(
+                            setup.resource_man.translates.gui
+                                [&setup.resource_man.registry.gui_ids.lbl_map_version]
+                                .as_str(),
+                            &[version.as_str()],
+                        );
+
+                        if version == VERSION {
+                            ui.label(label);
+                        } else {
+                            ui.colored_label(colors::ORANGE, label);
+                        }
+                    }
+
+                    if !map_info.namespaces.is_empty() {
+                        ui.label(format(
+                            setup.resource_man.translates.gui
+                                [&setup.resource_man.registry.gui_ids.lbl_map_namespaces]
+                                .as_str(),
+                            &[map_info.namespaces.join(", ").as_str()],
+                        ));
+                    }
+
+                    let missing = map_info.missing_namespaces(&setup.resource_man);
+                    if !missing.is_empty() {
+                        ui.colored_label(
+                            colors::RED,
+                            format(
+                                setup.resource_man.translates.gui[&setup
+                                    .resource_man
+                                    .registry
+                                    .gui_ids
+                                    .lbl_map_missing_namespaces]
+                                    .as_str(),
+                                &[missing.join(", ").as_str()],
+                            ),
+                        );
+                    }
+
+                    if map_info.is_too_new() {
+                        ui.colored_label(
+                            colors::RED,
+                            setup.resource_man.translates.gui
+                                [&setup.resource_man.registry.gui_ids.lbl_map_too_new]
+                                .as_str(),
+                        );
+                    }
+
                     ui.horizontal(|ui| {
                         if let Some(save_time) = map_info.save_time {
                             ui.label(format_time(
@@ -250,13 +383,14 @@ pub fn map_menu(setup: &mut GameSetup, context: &Context, loop_store: &mut Event
                             ));
                         }
 
-                        if ui
-                            .button(
-                                setup.resource_man.translates.gui
-                                    [&setup.resource_man.registry.gui_ids.btn_load]
-                                    .as_str(),
-                            )
-                            .clicked()
+                        if !map_info.is_too_new()
+                            && ui
+                                .button(
+                                    setup.resource_man.translates.gui
+                                        [&setup.resource_man.registry.gui_ids.btn_load]
+                                        .as_str(),
+                                )
+                                .clicked()
                         {
                             setup
                                 .game
@@ -281,6 +415,29 @@ pub fn map_menu(setup: &mut GameSetup, context: &Context, loop_store: &mut Event
 
                             dirty = true;
                         }
+
+                        if ui
+                            .button(
+                                setup.resource_man.translates.gui
+                                    [&setup.resource_man.registry.gui_ids.btn_backups]
+                                    .as_str(),
+                            )
+                            .clicked()
+                        {
+                            loop_store.map_backups = Map::backups(map_name);
+                            loop_store.popup_state = PopupState::MapRestore(map_name.clone());
+                        }
+
+                        if ui
+                            .button(
+                                setup.resource_man.translates.gui
+                                    [&setup.resource_man.registry.gui_ids.btn_export]
+                                    .as_str(),
+                            )
+                            .clicked()
+                        {
+                            export_map(setup, map_name);
+                        }
                     });
                 });
             }
@@ -308,6 +465,19 @@ pub fn map_menu(setup: &mut GameSetup, context: &Context, loop_store: &mut Event
             {
                 loop_store.popup_state = PopupState::MapCreate
             }
+            if ui
+                .button(
+                    RichText::new(
+                        setup.resource_man.translates.gui
+                            [&setup.resource_man.registry.gui_ids.btn_import]
+                            .as_str(),
+                    )
+                    .heading(),
+                )
+                .clicked()
+            {
+                import_map(setup);
+            }
             if ui
                 .button(
                     RichText::new(
@@ -33,6 +33,7 @@ pub enum PopupState {
     None,
     MapCreate,
     MapDeleteConfirmation(String),
+    MapRestore(String),
     InvalidName,
 }
 
@@ -1,9 +1,9 @@
+use std::collections::BTreeMap;
 use std::time::Instant;
 
 use egui::{Context, Rect, ScrollArea, Window};
-use futures::executor::block_on;
 
-use automancy::game::{GameMsg, TAKE_ITEM_ANIMATION_SPEED};
+use automancy::game::TAKE_ITEM_ANIMATION_SPEED;
 use automancy_defs::hashbrown::HashMap;
 use automancy_resources::data::item::Item;
 use automancy_resources::data::stack::ItemStack;
@@ -83,33 +83,57 @@ pub fn player(
                 .as_str(),
         );
 
-        if let Some(Data::Inventory(inventory)) = block_on(setup.game.call(
-            |reply| {
-                GameMsg::GetDataValue(setup.resource_man.registry.data_ids.player_inventory, reply)
-            },
-            None,
-        ))
-        .unwrap()
-        .unwrap()
-        {
+        let inventory = setup
+            .game_state
+            .borrow()
+            .data
+            .get(&setup.resource_man.registry.data_ids.player_inventory)
+            .cloned();
+
+        if let Some(Data::Inventory(inventory)) = inventory {
+            // items without a category come first, then each category by name
+            let mut categories = BTreeMap::<Option<&str>, Vec<ItemStack>>::new();
+
+            for (item, amount) in inventory.iter().flat_map(|(id, amount)| {
+                setup
+                    .resource_man
+                    .registry
+                    .item(*id)
+                    .map(|item| (*item, *amount))
+            }) {
+                categories
+                    .entry(
+                        item.category
+                            .map(|category| setup.resource_man.item_name(&category)),
+                    )
+                    .or_default()
+                    .push(ItemStack { item, amount });
+            }
+
             ScrollArea::vertical().show(ui, |ui| {
-                for (item, amount) in inventory.iter().flat_map(|(id, amount)| {
-                    setup
-                        .resource_man
-                        .registry
-                        .item(*id)
-                        .map(|item| (*item, *amount))
-                }) {
-                    let (dst_rect, _) = draw_item(
-                        &setup.resource_man,
-                        ui,
-                        item_instances,
-                        None,
-                        ItemStack { item, amount },
-                        MEDIUM_ITEM_ICON_SIZE,
-                    );
-
-                    take_item_animation(item, dst_rect, setup, loop_store, item_instances);
+                for (category, stacks) in categories {
+                    if let Some(category) = category {
+                        ui.label(category);
+                    }
+
+                    for stack in stacks {
+                        let (dst_rect, _) = draw_item(
+                            &setup.resource_man,
+                            ui,
+                            item_instances,
+                            None,
+                            stack,
+                            MEDIUM_ITEM_ICON_SIZE,
+                        );
+
+                        take_item_animation(
+                            stack.item,
+                            dst_rect,
+                            setup,
+                            loop_store,
+                            item_instances,
+                        );
+                    }
                 }
             });
         }
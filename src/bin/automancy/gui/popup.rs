@@ -6,6 +6,8 @@ use automancy::game::GameMsg;
 use automancy::map::Map;
 use automancy_defs::gui::Gui;
 use automancy_defs::log;
+use automancy_resources::format_time;
+use automancy_resources::worldgen::seed_from_text;
 
 use crate::event::EventLoopStorage;
 use crate::gui::{default_frame, GuiState, PopupState};
@@ -89,6 +91,80 @@ pub fn map_delete_popup(
     }
 }
 
+/// Draws the popup that restores a map from one of its backups.
+pub fn map_restore_popup(
+    setup: &mut GameSetup,
+    gui: &mut Gui,
+    loop_store: &mut EventLoopStorage,
+    map_name: &str,
+) {
+    let mut dirty = false;
+
+    Window::new(
+        setup.resource_man.translates.gui[&setup.resource_man.registry.gui_ids.restore_map]
+            .as_str(),
+    )
+    .resizable(false)
+    .collapsible(false)
+    .default_width(250.0)
+    .anchor(Align2::CENTER_CENTER, vec2(0.0, 0.0))
+    .frame(default_frame())
+    .show(&gui.context, |ui| {
+        if loop_store.map_backups.is_empty() {
+            ui.label(
+                setup.resource_man.translates.gui
+                    [&setup.resource_man.registry.gui_ids.lbl_no_backups]
+                    .as_str(),
+            );
+        }
+
+        for (index, map_info) in &loop_store.map_backups {
+            ui.horizontal(|ui| {
+                if let Some(save_time) = map_info.save_time {
+                    ui.label(format_time(
+                        save_time,
+                        setup.resource_man.translates.gui
+                            [&setup.resource_man.registry.gui_ids.time_fmt]
+                            .as_str(),
+                    ));
+                }
+
+                if ui
+                    .button(
+                        setup.resource_man.translates.gui
+                            [&setup.resource_man.registry.gui_ids.btn_restore]
+                            .as_str(),
+                    )
+                    .clicked()
+                {
+                    match Map::restore_backup(map_name, *index, setup.options.game.backup_count) {
+                        Ok(()) => log::info!("Restored map {map_name} from backup {index}!"),
+                        Err(e) => log::error!("Could not restore map {map_name}: {e:?}"),
+                    }
+
+                    dirty = true;
+                }
+            });
+        }
+
+        if ui
+            .button(
+                setup.resource_man.translates.gui[&setup.resource_man.registry.gui_ids.btn_cancel]
+                    .as_str(),
+            )
+            .clicked()
+        {
+            loop_store.popup_state = PopupState::None
+        }
+    });
+
+    if dirty {
+        loop_store.map_backups.clear();
+        loop_store.popup_state = PopupState::None;
+        setup.refresh_maps();
+    }
+}
+
 /// Draws the map creation popup.
 pub fn map_create_popup(setup: &GameSetup, gui: &mut Gui, loop_store: &mut EventLoopStorage) {
     Window::new(
@@ -104,6 +180,20 @@ pub fn map_create_popup(setup: &GameSetup, gui: &mut Gui, loop_store: &mut Event
             ui.label("Name:"); //TODO add this to translation
             ui.text_edit_singleline(&mut loop_store.map_name_input);
         });
+        ui.horizontal(|ui| {
+            ui.label(
+                setup.resource_man.translates.gui
+                    [&setup.resource_man.registry.gui_ids.lbl_map_seed]
+                    .as_str(),
+            );
+            ui.text_edit_singleline(&mut loop_store.map_seed_input);
+        });
+        ui.label(
+            setup.resource_man.translates.gui
+                [&setup.resource_man.registry.gui_ids.lbl_map_description]
+                .as_str(),
+        );
+        ui.text_edit_multiline(&mut loop_store.map_description_input);
         if ui
             .button(
                 setup.resource_man.translates.gui[&setup.resource_man.registry.gui_ids.btn_confirm]
@@ -112,11 +202,19 @@ pub fn map_create_popup(setup: &GameSetup, gui: &mut Gui, loop_store: &mut Event
             .clicked()
         {
             let name = Map::sanitize_name(loop_store.map_name_input.clone());
+            let seed = seed_from_text(&loop_store.map_seed_input);
             setup
                 .game
-                .send_message(GameMsg::LoadMap(setup.resource_man.clone(), name))
+                .send_message(GameMsg::CreateMap(
+                    setup.resource_man.clone(),
+                    name,
+                    seed,
+                    loop_store.map_description_input.trim().to_string(),
+                ))
                 .unwrap();
             loop_store.map_name_input.clear();
+            loop_store.map_seed_input.clear();
+            loop_store.map_description_input.clear();
             loop_store.popup_state = PopupState::None;
             loop_store.switch_gui_state(GuiState::Ingame);
         }
@@ -2,8 +2,6 @@ use std::time::Instant;
 
 use egui::Context;
 use egui::{vec2, DragValue, Margin, Ui, Window};
-use futures::executor::block_on;
-use ractor::ActorRef;
 
 use automancy::game::GameMsg;
 use automancy::tile_entity::TileEntityMsg;
@@ -14,6 +12,7 @@ use automancy_defs::math::Float;
 use automancy_resources::data::inventory::Inventory;
 use automancy_resources::data::stack::ItemStack;
 use automancy_resources::data::{Data, DataMap};
+use automancy_resources::script::{Output, CERTAIN};
 use automancy_resources::tile::Tile;
 use automancy_resources::ResourceManager;
 
@@ -23,6 +22,14 @@ use crate::gui::searchable_id;
 use crate::renderer::GuiInstances;
 use crate::setup::GameSetup;
 
+/// Sends a message to the tile entity at the given position, through the game.
+fn send_to_tile(setup: &GameSetup, coord: TileCoord, message: TileEntityMsg) {
+    setup
+        .game
+        .send_message(GameMsg::ForwardMsgToTile(coord, message))
+        .unwrap();
+}
+
 /// Draws the direction selector.
 pub fn add_direction(ui: &mut Ui, target_coord: &mut Option<TileCoord>, n: usize) {
     let coord = TileHex::NEIGHBORS[(n + 2) % 6];
@@ -43,22 +50,19 @@ pub fn add_direction(ui: &mut Ui, target_coord: &mut Option<TileCoord>, n: usize
     );
 }
 
+/// Draws the direction selector for the target stored under the given key.
 fn config_target(
     ui: &mut Ui,
     setup: &GameSetup,
     data: &DataMap,
-    tile_entity: ActorRef<TileEntityMsg>,
+    coord: TileCoord,
+    key: Id,
+    label: Id,
 ) {
-    let current_target_coord = data
-        .get(&setup.resource_man.registry.data_ids.target)
-        .and_then(Data::as_coord)
-        .cloned();
+    let current_target_coord = data.get(&key).and_then(Data::as_coord).cloned();
     let mut new_target_coord = current_target_coord;
 
-    ui.label(
-        setup.resource_man.translates.gui[&setup.resource_man.registry.gui_ids.tile_config_target]
-            .as_str(),
-    );
+    ui.label(setup.resource_man.translates.gui[&label].as_str());
 
     ui.vertical(|ui| {
         ui.horizontal(|ui| {
@@ -82,18 +86,13 @@ fn config_target(
 
     if new_target_coord != current_target_coord {
         if let Some(target_coord) = new_target_coord {
-            tile_entity
-                .send_message(TileEntityMsg::SetDataValue(
-                    setup.resource_man.registry.data_ids.target,
-                    Data::Coord(target_coord),
-                ))
-                .unwrap();
+            send_to_tile(
+                setup,
+                coord,
+                TileEntityMsg::SetDataValue(key, Data::Coord(target_coord)),
+            );
         } else {
-            tile_entity
-                .send_message(TileEntityMsg::RemoveData(
-                    setup.resource_man.registry.data_ids.target,
-                ))
-                .unwrap();
+            send_to_tile(setup, coord, TileEntityMsg::RemoveData(key));
         }
     }
 }
@@ -127,7 +126,7 @@ fn config_amount(
     ui: &mut Ui,
     setup: &GameSetup,
     data: &DataMap,
-    tile_entity: ActorRef<TileEntityMsg>,
+    coord: TileCoord,
     tile_info: &Tile,
 ) {
     let current_amount = data
@@ -155,12 +154,14 @@ fn config_amount(
     }
 
     if new_amount != current_amount {
-        tile_entity
-            .send_message(TileEntityMsg::SetDataValue(
+        send_to_tile(
+            setup,
+            coord,
+            TileEntityMsg::SetDataValue(
                 setup.resource_man.registry.data_ids.amount,
                 Data::Amount(new_amount),
-            ))
-            .unwrap();
+            ),
+        );
     }
 }
 
@@ -169,46 +170,40 @@ fn takeable_item(
     setup: &GameSetup,
     loop_store: &mut EventLoopStorage,
     item_instances: &mut GuiInstances,
-    mut buffer: Inventory,
-    game_data: &mut DataMap,
-    tile_entity: ActorRef<TileEntityMsg>,
+    buffer: Inventory,
+    coord: TileCoord,
 ) {
-    if let Some(inventory) = game_data
-        .entry(setup.resource_man.registry.data_ids.player_inventory)
-        .or_insert_with(Data::new_inventory)
-        .as_inventory_mut()
-    {
-        for (id, amount) in buffer.clone().into_inner() {
-            let item = *setup.resource_man.registry.item(id).unwrap();
-
-            let (rect, response) = draw_item(
-                &setup.resource_man,
-                ui,
-                item_instances,
-                None,
-                ItemStack { item, amount },
-                MEDIUM_ITEM_ICON_SIZE,
+    for (id, amount) in buffer.into_inner() {
+        let item = *setup.resource_man.registry.item(id).unwrap();
+
+        let (rect, response) = draw_item(
+            &setup.resource_man,
+            ui,
+            item_instances,
+            None,
+            ItemStack { item, amount },
+            MEDIUM_ITEM_ICON_SIZE,
+        );
+
+        if response.clicked() && amount > 0 {
+            // the tile takes what it still has, since it may have changed since the snapshot
+            send_to_tile(
+                setup,
+                coord,
+                TileEntityMsg::TakeItem {
+                    key: setup.resource_man.registry.data_ids.buffer, //TODO rename "tile config"
+                    item: id,
+                    amount,
+                },
             );
 
-            if response.clicked() {
-                if let Some(amount) = buffer.take(id, amount) {
-                    inventory.add(id, amount);
-                    loop_store
-                        .take_item_animations
-                        .entry(item)
-                        .or_insert_with(Default::default)
-                        .push_back((Instant::now(), rect));
-                }
-            }
+            loop_store
+                .take_item_animations
+                .entry(item)
+                .or_insert_with(Default::default)
+                .push_back((Instant::now(), rect));
         }
     }
-
-    tile_entity
-        .send_message(TileEntityMsg::SetDataValue(
-            setup.resource_man.registry.data_ids.buffer, //TODO rename "tile config"
-            Data::Inventory(buffer),
-        ))
-        .unwrap();
 }
 
 fn config_item(
@@ -218,7 +213,7 @@ fn config_item(
     item_instances: &mut GuiInstances,
     data: &DataMap,
     item_type: Id,
-    tile_entity: ActorRef<TileEntityMsg>,
+    coord: TileCoord,
     tile_info: &Tile,
 ) {
     let current_item = data
@@ -241,7 +236,7 @@ fn config_item(
                 .as_str(),
         );
 
-        config_amount(ui, setup, data, tile_entity.clone(), tile_info);
+        config_amount(ui, setup, data, coord, tile_info);
     });
 
     if let Some(stack) = current_item
@@ -271,21 +266,47 @@ fn config_item(
 
     if new_item != current_item {
         if let Some(item) = new_item {
-            tile_entity
-                .send_message(TileEntityMsg::SetDataValue(
+            send_to_tile(
+                setup,
+                coord,
+                TileEntityMsg::SetDataValue(
                     setup.resource_man.registry.data_ids.item,
                     Data::Id(item),
-                ))
-                .unwrap();
-            tile_entity
-                .send_message(TileEntityMsg::RemoveData(
-                    setup.resource_man.registry.data_ids.buffer,
-                ))
-                .unwrap();
+                ),
+            );
+            send_to_tile(
+                setup,
+                coord,
+                TileEntityMsg::RemoveData(setup.resource_man.registry.data_ids.buffer),
+            );
         }
     }
 }
 
+/// Draws a script output, with its chance if it does not always come out.
+fn draw_output(
+    setup: &GameSetup,
+    ui: &mut Ui,
+    item_instances: &mut GuiInstances,
+    prefix: &'static str,
+    output: &Output,
+) {
+    ui.horizontal(|ui| {
+        draw_item(
+            &setup.resource_man,
+            ui,
+            item_instances,
+            Some(prefix),
+            output.stack,
+            SMALL_ITEM_ICON_SIZE,
+        );
+
+        if output.chance < CERTAIN {
+            ui.label(format!("{}.{}%", output.chance / 10, output.chance % 10));
+        }
+    });
+}
+
 fn config_script(
     ui: &mut Ui,
     setup: &GameSetup,
@@ -293,7 +314,7 @@ fn config_script(
     item_instances: &mut GuiInstances,
     data: &DataMap,
     scripts: &Vec<Id>,
-    tile_entity: ActorRef<TileEntityMsg>,
+    coord: TileCoord,
 ) {
     let current_script = data
         .get(&setup.resource_man.registry.data_ids.script)
@@ -312,26 +333,41 @@ fn config_script(
         if let Some(script) = new_script.and_then(|id| setup.resource_man.registry.script(id)) {
             if let Some(inputs) = &script.instructions.inputs {
                 for input in inputs {
+                    let mut input = *input;
+
+                    // a tag is named as itself, and shown with the icon of its first item
+                    if setup
+                        .resource_man
+                        .registry
+                        .tags
+                        .contains_key(&input.item.id)
+                    {
+                        if let Some(item) = setup
+                            .resource_man
+                            .get_items(input.item.id, &mut loop_store.tag_cache)
+                            .first()
+                        {
+                            input.item.model = item.model;
+                        }
+                    }
+
                     draw_item(
                         &setup.resource_man,
                         ui,
                         item_instances,
                         Some(" + "),
-                        *input,
+                        input,
                         SMALL_ITEM_ICON_SIZE,
                     );
                 }
             }
 
             for output in &script.instructions.outputs {
-                draw_item(
-                    &setup.resource_man,
-                    ui,
-                    item_instances,
-                    Some("=> "),
-                    *output,
-                    SMALL_ITEM_ICON_SIZE,
-                );
+                draw_output(setup, ui, item_instances, "=> ", output);
+            }
+
+            for byproduct in &script.instructions.byproducts {
+                draw_output(setup, ui, item_instances, "~> ", byproduct);
             }
         }
     });
@@ -348,17 +384,19 @@ fn config_script(
 
     if new_script != current_script {
         if let Some(script) = new_script {
-            tile_entity
-                .send_message(TileEntityMsg::SetDataValue(
+            send_to_tile(
+                setup,
+                coord,
+                TileEntityMsg::SetDataValue(
                     setup.resource_man.registry.data_ids.script,
                     Data::Id(script),
-                ))
-                .unwrap();
-            tile_entity
-                .send_message(TileEntityMsg::RemoveData(
-                    setup.resource_man.registry.data_ids.buffer,
-                ))
-                .unwrap();
+                ),
+            );
+            send_to_tile(
+                setup,
+                coord,
+                TileEntityMsg::RemoveData(setup.resource_man.registry.data_ids.buffer),
+            );
         }
     }
 }
@@ -371,31 +409,17 @@ pub fn tile_config(
     context: &Context,
 ) {
     if let Some(config_open) = loop_store.config_open {
-        let mut game_data = block_on(setup.game.call(GameMsg::TakeDataMap, None))
-            .unwrap()
-            .unwrap();
-
-        let tile = block_on(
-            setup
-                .game
-                .call(|reply| GameMsg::GetTile(config_open, reply), None),
-        )
-        .unwrap()
-        .unwrap();
+        let (tile, data) = {
+            let game_state = setup.game_state.borrow();
+            let coord = game_state.owner(config_open);
 
-        let tile_entity = block_on(
-            setup
-                .game
-                .call(|reply| GameMsg::GetTileEntity(config_open, reply), None),
-        )
-        .unwrap()
-        .unwrap();
-
-        if let Some(((id, _), tile_entity)) = tile.zip(tile_entity) {
-            let data = block_on(tile_entity.call(TileEntityMsg::GetData, None))
-                .unwrap()
-                .unwrap();
+            (
+                game_state.tiles.get(&coord).cloned(),
+                game_state.tile_data.get(&coord).cloned(),
+            )
+        };
 
+        if let Some(((id, _), data)) = tile.zip(data) {
             Window::new(
                 setup.resource_man.translates.gui[&setup.resource_man.registry.gui_ids.tile_config]
                     .to_string(),
@@ -425,7 +449,7 @@ pub fn tile_config(
                             item_instances,
                             &data,
                             scripts,
-                            tile_entity.clone(),
+                            config_open,
                         );
                     });
                     ui.add_space(MARGIN);
@@ -452,8 +476,7 @@ pub fn tile_config(
                                 loop_store,
                                 item_instances,
                                 buffer,
-                                &mut game_data,
-                                tile_entity.clone(),
+                                config_open,
                             );
                         });
                         ui.add_space(MARGIN);
@@ -474,7 +497,7 @@ pub fn tile_config(
                             item_instances,
                             &data,
                             item_type,
-                            tile_entity.clone(),
+                            config_open,
                             tile_info,
                         );
                     });
@@ -491,7 +514,39 @@ pub fn tile_config(
                 {
                     ui.add_space(MARGIN);
                     ui.vertical(|ui| {
-                        config_target(ui, setup, &data, tile_entity.clone());
+                        config_target(
+                            ui,
+                            setup,
+                            &data,
+                            config_open,
+                            setup.resource_man.registry.data_ids.target,
+                            setup.resource_man.registry.gui_ids.tile_config_target,
+                        );
+                    });
+                    ui.add_space(MARGIN);
+                }
+
+                if data
+                    .get(&setup.resource_man.registry.data_ids.script)
+                    .and_then(Data::as_id)
+                    .and_then(|script| setup.resource_man.registry.script(*script))
+                    .map(|script| !script.instructions.byproducts.is_empty())
+                    .unwrap_or(false)
+                {
+                    ui.add_space(MARGIN);
+                    ui.vertical(|ui| {
+                        config_target(
+                            ui,
+                            setup,
+                            &data,
+                            config_open,
+                            setup.resource_man.registry.data_ids.byproduct_target,
+                            setup
+                                .resource_man
+                                .registry
+                                .gui_ids
+                                .tile_config_byproduct_target,
+                        );
                     });
                     ui.add_space(MARGIN);
                 }
@@ -512,10 +567,5 @@ pub fn tile_config(
                 }
             });
         }
-
-        setup
-            .game
-            .send_message(GameMsg::SetDataMap(game_data))
-            .unwrap();
     }
 }
@@ -8,6 +8,7 @@ use automancy_defs::hashbrown::HashMap;
 use automancy_defs::id::Id;
 use automancy_defs::math::{rad, Matrix4};
 use automancy_defs::rendering::InstanceData;
+use automancy_resources::data::Data;
 
 use crate::gui::default_frame;
 use crate::renderer::GuiInstances;
@@ -27,6 +28,15 @@ fn draw_tile_selection(
         .resource_man
         .ordered_tiles
         .iter()
+        .filter(|id| {
+            !setup
+                .resource_man
+                .registry
+                .tile_data(**id, setup.resource_man.registry.data_ids.not_placeable)
+                .and_then(Data::as_bool)
+                .cloned()
+                .unwrap_or(false)
+        })
         .flat_map(|id| {
             setup
                 .resource_man
@@ -5,7 +5,6 @@ use std::time::Instant;
 use arboard::{Clipboard, ImageData};
 use egui::{Rect, Rgba};
 use egui_wgpu::renderer::ScreenDescriptor;
-use futures::executor::block_on;
 use image::{EncodableLayout, RgbaImage};
 use num::PrimInt;
 use tokio::sync::oneshot;
@@ -18,14 +17,15 @@ use wgpu::{
     COPY_BYTES_PER_ROW_ALIGNMENT,
 };
 
-use automancy::game::{GameMsg, RenderUnit, TransactionRecord, TRANSACTION_ANIMATION_SPEED};
+use automancy::game::{GameMsg, RenderSnapshot, RenderUnit, RenderView, TransactionRecord};
 use automancy::gpu;
 use automancy::gpu::{Gpu, NORMAL_CLEAR, SCREENSHOT_FORMAT, UPSCALE_LEVEL};
 use automancy::input::KeyActions;
+use automancy::map::TileData;
 use automancy_defs::cgmath::{point3, vec3, EuclideanSpace};
 use automancy_defs::coord::TileCoord;
 use automancy_defs::gui::Gui;
-use automancy_defs::hashbrown::HashMap;
+use automancy_defs::hashbrown::{HashMap, HashSet};
 use automancy_defs::id::Id;
 use automancy_defs::math::{deg, direction_to_angle, is_in_culling_range, Float, Matrix4, FAR};
 use automancy_defs::rendering::{
@@ -38,11 +38,66 @@ use crate::setup::GameSetup;
 
 pub struct Renderer {
     pub gpu: Gpu,
+    /// the tiles in view, as of the last snapshot received from the game
+    cache: RenderCache,
+    /// the view and last applied snapshot the game was last told about
+    told: Option<(RenderView, Option<(u64, RenderView)>)>,
 }
 
 impl Renderer {
     pub fn new(gpu: Gpu) -> Self {
-        Self { gpu }
+        Self {
+            gpu,
+            cache: Default::default(),
+            told: None,
+        }
+    }
+}
+
+/// The tiles and tile data the renderer has received from the game.
+#[derive(Default)]
+struct RenderCache {
+    /// the version and view of the last snapshot applied
+    last: Option<(u64, RenderView)>,
+    instances: HashMap<TileCoord, RenderUnit>,
+    /// the terrain in view, drawn in its own pass under the tiles
+    terrain: HashMap<TileCoord, RenderUnit>,
+    data: TileData,
+}
+
+impl RenderCache {
+    /// Applies a snapshot from the game, and forgets whatever went out of view.
+    fn apply(&mut self, snapshot: &RenderSnapshot) {
+        let view = snapshot.view;
+
+        if snapshot.full {
+            self.instances.clear();
+            self.terrain.clear();
+            self.data.clear();
+        }
+
+        for (coord, unit) in &snapshot.tiles {
+            if let Some(unit) = unit {
+                self.instances.insert(*coord, *unit);
+            } else {
+                self.instances.remove(coord);
+                self.data.remove(coord);
+            }
+        }
+        for (coord, unit) in &snapshot.terrain {
+            if let Some(unit) = unit {
+                self.terrain.insert(*coord, *unit);
+            } else {
+                self.terrain.remove(coord);
+            }
+        }
+        self.data.extend(snapshot.data.clone());
+
+        self.instances.retain(|coord, _| view.contains(*coord));
+        self.terrain.retain(|coord, _| view.contains(*coord));
+        self.data.retain(|coord, _| view.contains(*coord));
+
+        self.last = Some((snapshot.version, view));
     }
 }
 
@@ -62,6 +117,11 @@ fn get_angle_from_direction(target: &Data) -> Option<Float> {
     }
 }
 
+/// Where along a tile the first queued item is drawn, from its center (0) to the tile it goes to (1).
+const QUEUE_FRONT: f64 = 0.4;
+/// How far apart queued items are drawn, in the same units.
+const QUEUE_SPACING: f64 = 0.2;
+
 pub type GuiInstances = Vec<(InstanceData, Id, (Option<Rect>, Option<Rect>))>;
 
 impl Renderer {
@@ -96,18 +156,42 @@ impl Renderer {
         let camera_pos = setup.camera.get_pos();
         let camera_pos_float = camera_pos.cast::<Float>().unwrap();
 
-        let map_render_info = block_on(setup.game.call(
-            |reply| GameMsg::RenderInfoRequest {
-                culling_range,
-                center: camera_coord,
-                reply,
-            },
-            None,
-        ))
-        .unwrap()
-        .unwrap();
+        let view = RenderView {
+            center: camera_coord,
+            culling_range,
+        };
 
-        let (mut instances, all_data) = map_render_info;
+        let (snapshot, transaction_records) = {
+            let game_state = setup.game_state.borrow();
+
+            (
+                game_state.render.clone(),
+                game_state.transaction_records.clone(),
+            )
+        };
+
+        // a snapshot only applies on top of the one it was made from, and only once
+        if let Some(snapshot) = snapshot.filter(|snapshot| {
+            self.cache.last != Some((snapshot.version, snapshot.view))
+                && (snapshot.full || snapshot.since == self.cache.last)
+        }) {
+            self.cache.apply(&snapshot);
+        }
+
+        if self.told != Some((view, self.cache.last)) {
+            self.told = Some((view, self.cache.last));
+
+            setup
+                .game
+                .send_message(GameMsg::SetRenderView {
+                    view,
+                    last: self.cache.last,
+                })
+                .unwrap();
+        }
+
+        let mut instances = self.cache.instances.clone();
+        let all_data = &self.cache.data;
 
         for (coord, instance) in instances.iter_mut() {
             if let Some(theta) = all_data
@@ -131,7 +215,7 @@ impl Renderer {
             }
         }
 
-        for (coord, data) in all_data {
+        for (&coord, data) in all_data {
             if let Some(link) = data
                 .get(&setup.resource_man.registry.data_ids.link)
                 .and_then(Data::as_coord)
@@ -150,22 +234,54 @@ impl Renderer {
                     setup.resource_man.registry.model_ids.cube1x1,
                 ));
             }
+
+            if let Some((queue, target)) = data
+                .get(&setup.resource_man.registry.data_ids.queue)
+                .and_then(Data::as_vec_stack)
+                .zip(
+                    data.get(&setup.resource_man.registry.data_ids.target)
+                        .and_then(Data::as_coord),
+                )
+            {
+                let theta = direction_to_angle(math::hex_to_pixel((*target).into()).to_vec());
+
+                for (index, stack) in queue.iter().enumerate() {
+                    // the first item in the queue waits at the edge it leaves from
+                    let t = QUEUE_FRONT - index as f64 * QUEUE_SPACING;
+                    let point = lerp_coords_to_pixel(coord, coord + *target, t);
+
+                    let instance = InstanceData::default()
+                        .with_model_matrix(
+                            Matrix4::from_translation(vec3(
+                                point.x as Float,
+                                point.y as Float,
+                                FAR as Float,
+                            )) * Matrix4::from_angle_z(theta)
+                                * Matrix4::from_scale(0.3),
+                        )
+                        .with_light_pos(camera_pos_float);
+                    let model = setup.resource_man.get_item_model(stack.item);
+
+                    in_world_item_instances.push((instance, model));
+                }
+            }
         }
 
-        let transaction_records_mutex =
-            block_on(setup.game.call(GameMsg::GetRecordedTransactions, None))
-                .unwrap()
-                .unwrap();
-        let transaction_records = transaction_records_mutex.lock().unwrap();
+        let transaction_records = transaction_records.lock().unwrap();
         let now = Instant::now();
 
         for ((source_coord, coord), instants) in transaction_records.iter() {
             if is_in_culling_range(camera_coord, *source_coord, culling_range)
                 && is_in_culling_range(camera_coord, *coord, culling_range)
             {
-                for (instant, TransactionRecord { stack, .. }) in instants {
-                    let duration = now.duration_since(*instant);
-                    let t = duration.as_secs_f64() / TRANSACTION_ANIMATION_SPEED.as_secs_f64();
+                for (
+                    instant,
+                    TransactionRecord {
+                        stack, duration, ..
+                    },
+                ) in instants
+                {
+                    let t = now.duration_since(*instant).as_secs_f64() / duration.as_secs_f64();
 
                     let point = lerp_coords_to_pixel(*source_coord, *coord, t);
 
@@ -190,7 +306,7 @@ impl Renderer {
             }
         }
 
-        let mut instances = {
+        let (terrain_instances, mut instances) = {
             let none = setup
                 .resource_man
                 .registry
@@ -204,14 +320,34 @@ impl Renderer {
             let r0 = camera_coord.r() - culling_range.1 / 2;
             let r1 = camera_coord.r() + culling_range.1 / 2;
 
+            // the other positions of bigger tiles are drawn by the tile itself
+            let covered = instances
+                .iter()
+                .flat_map(|(coord, unit)| {
+                    setup
+                        .resource_man
+                        .registry
+                        .tile(unit.tile)
+                        .into_iter()
+                        .flat_map(move |tile| {
+                            tile.footprint.iter().map(move |offset| *coord + *offset)
+                        })
+                })
+                .collect::<HashSet<_>>();
+
+            // the terrain is drawn under the tiles too, so deposits stay visible under them
+            let mut terrain = HashMap::new();
+
             for q in q0..q1 {
                 for r in r0..r1 {
                     let coord = TileCoord::new(q, r);
 
-                    if !instances.contains_key(&coord) {
+                    if let Some(unit) = self.cache.terrain.get(&coord) {
+                        terrain.insert(coord, *unit);
+                    } else if !instances.contains_key(&coord) && !covered.contains(&coord) {
                         let p = math::hex_to_pixel(coord.into());
 
-                        instances.insert(
+                        terrain.insert(
                             coord,
                             RenderUnit {
                                 instance: InstanceData::default().with_model_matrix(
@@ -230,23 +366,29 @@ impl Renderer {
             }
 
             for (coord, color) in tile_tints.into_iter() {
-                if let Some(RenderUnit { instance, .. }) = instances.get_mut(&coord) {
-                    *instance = instance.with_color_offset(color.to_array())
+                for units in [&mut instances, &mut terrain] {
+                    if let Some(RenderUnit { instance, .. }) = units.get_mut(&coord) {
+                        *instance = instance.with_color_offset(color.to_array())
+                    }
                 }
             }
 
-            let mut map = HashMap::new();
+            let group = |units: HashMap<TileCoord, RenderUnit>| {
+                let mut map = HashMap::new();
 
-            for RenderUnit {
-                instance, model, ..
-            } in instances.into_values()
-            {
-                map.entry(model)
-                    .or_insert_with(|| Vec::with_capacity(32))
-                    .push((instance.with_light_pos(camera_pos_float), model, ()))
-            }
+                for RenderUnit {
+                    instance, model, ..
+                } in units.into_values()
+                {
+                    map.entry(model)
+                        .or_insert_with(|| Vec::with_capacity(32))
+                        .push((instance.with_light_pos(camera_pos_float), model, ()))
+                }
 
-            map.into_values().flatten().collect::<Vec<_>>()
+                map.into_values().flatten().collect::<Vec<_>>()
+            };
+
+            (group(terrain), group(instances))
         };
 
         extra_instances.sort_by_key(|v| v.1);
@@ -275,6 +417,7 @@ impl Renderer {
             setup,
             gui,
             matrix,
+            &terrain_instances,
             &instances,
             &overlay_instances,
             &in_world_item_instances,
@@ -288,6 +431,7 @@ impl Renderer {
         setup: &GameSetup,
         gui: &mut Gui,
         matrix: Matrix4,
+        terrain_instances: &[(InstanceData, Id, ())],
         instances: &[(InstanceData, Id, ())],
         overlay_instances: &[(InstanceData, Id, ())],
         in_world_item_instances: &[(InstanceData, Id, ())],
@@ -314,14 +458,20 @@ impl Renderer {
                 label: Some("Render Encoder"),
             });
 
+        self.gpu.queue.write_buffer(
+            &self.gpu.game_resources.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[GameUBO::new(matrix)]),
+        );
+
         {
             let (raw_instances, indirect_commands, draw_count) =
-                gpu::indirect_instance(&setup.resource_man, instances, true);
+                gpu::indirect_instance(&setup.resource_man, terrain_instances, true);
 
             gpu::create_or_write_buffer(
                 &self.gpu.device,
                 &self.gpu.queue,
-                &mut self.gpu.game_resources.instance_buffer,
+                &mut self.gpu.terrain_resources.instance_buffer,
                 bytemuck::cast_slice(raw_instances.as_slice()),
             );
             let mut indirect_buffer = vec![];
@@ -332,12 +482,12 @@ impl Renderer {
             gpu::create_or_write_buffer(
                 &self.gpu.device,
                 &self.gpu.queue,
-                &mut self.gpu.game_resources.indirect_buffer,
+                &mut self.gpu.terrain_resources.indirect_buffer,
                 indirect_buffer.as_slice(),
             );
 
-            let mut game_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("Game Render Pass"),
+            let mut terrain_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Terrain Render Pass"),
                 color_attachments: &[
                     Some(RenderPassColorAttachment {
                         view: &self.gpu.game_texture().1,
@@ -367,12 +517,82 @@ impl Renderer {
             });
 
             if draw_count > 0 {
-                self.gpu.queue.write_buffer(
-                    &self.gpu.game_resources.uniform_buffer,
+                terrain_pass.set_viewport(
+                    0.0,
+                    0.0,
+                    (size.width * UPSCALE_LEVEL) as Float,
+                    (size.height * UPSCALE_LEVEL) as Float,
+                    1.0,
+                    0.0,
+                );
+                terrain_pass.set_pipeline(&self.gpu.game_resources.pipeline);
+                terrain_pass.set_bind_group(0, &self.gpu.game_resources.bind_group, &[]);
+                terrain_pass.set_vertex_buffer(0, self.gpu.vertex_buffer.slice(..));
+                terrain_pass
+                    .set_vertex_buffer(1, self.gpu.terrain_resources.instance_buffer.slice(..));
+                terrain_pass.set_index_buffer(self.gpu.index_buffer.slice(..), IndexFormat::Uint16);
+
+                terrain_pass.multi_draw_indexed_indirect(
+                    &self.gpu.terrain_resources.indirect_buffer,
                     0,
-                    bytemuck::cast_slice(&[GameUBO::new(matrix)]),
+                    draw_count,
                 );
+            }
+        }
+
+        {
+            let (raw_instances, indirect_commands, draw_count) =
+                gpu::indirect_instance(&setup.resource_man, instances, true);
+
+            gpu::create_or_write_buffer(
+                &self.gpu.device,
+                &self.gpu.queue,
+                &mut self.gpu.game_resources.instance_buffer,
+                bytemuck::cast_slice(raw_instances.as_slice()),
+            );
+            let mut indirect_buffer = vec![];
+            indirect_commands
+                .into_iter()
+                .flat_map(|v| v.1)
+                .for_each(|v| indirect_buffer.extend_from_slice(v.0.as_bytes()));
+            gpu::create_or_write_buffer(
+                &self.gpu.device,
+                &self.gpu.queue,
+                &mut self.gpu.game_resources.indirect_buffer,
+                indirect_buffer.as_slice(),
+            );
 
+            let mut game_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Game Render Pass"),
+                color_attachments: &[
+                    Some(RenderPassColorAttachment {
+                        view: &self.gpu.game_texture().1,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Load,
+                            store: true,
+                        },
+                    }),
+                    Some(RenderPassColorAttachment {
+                        view: &self.gpu.normal_texture().1,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Load,
+                            store: true,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &self.gpu.depth_texture().1,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            if draw_count > 0 {
                 game_pass.set_viewport(
                     0.0,
                     0.0,
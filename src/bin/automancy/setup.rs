@@ -1,12 +1,15 @@
 use std::fs;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use egui::Frame;
 use ractor::concurrency::JoinHandle;
 use ractor::{Actor, ActorRef};
+use tokio::sync::watch;
 
 use automancy::camera::Camera;
-use automancy::game::{Game, GameMsg, TICK_INTERVAL};
+use automancy::game::{Game, GameMsg, GameSnapshot, TICK_INTERVAL};
 use automancy::input::InputHandler;
 use automancy::map::{Map, MapInfo, MAIN_MENU, MAP_PATH};
 use automancy::options::Options;
@@ -24,36 +27,10 @@ use crate::gui;
 fn load_resources(track: TrackHandle) -> (Arc<ResourceManager>, Vec<Vertex>, Vec<u16>) {
     let mut resource_man = ResourceManager::new(track);
 
-    fs::read_dir(RESOURCES_PATH)
-        .expect("The resources folder doesn't exist- this is very wrong")
-        .flatten()
-        .map(|v| v.path())
-        .for_each(|dir| {
-            let namespace = dir.file_name().unwrap().to_str().unwrap();
-            log::info!("loading namespace {namespace}...");
-            resource_man
-                .load_models(&dir)
-                .expect("Error loading models");
-            resource_man.load_audio(&dir).expect("Error loading audio");
-            resource_man.load_tiles(&dir).expect("Error loading tiles");
-            resource_man.load_items(&dir).expect("Error loading items");
-            resource_man.load_tags(&dir).expect("Error loading tags");
-            resource_man
-                .load_scripts(&dir)
-                .expect("Error loading scripts");
-            resource_man
-                .load_translates(&dir)
-                .expect("Error loading translates");
-            resource_man
-                .load_shaders(&dir)
-                .expect("Error loading shaders");
-            resource_man
-                .load_functions(&dir)
-                .expect("Error loading functions");
-            log::info!("loaded namespace {namespace}.");
-        });
-
-    resource_man.ordered_items();
+    resource_man
+        .load_all(Path::new(RESOURCES_PATH))
+        .expect("Error loading resources");
+
     let (vertices, indices) = resource_man.compile_models();
 
     (Arc::new(resource_man), vertices, indices)
@@ -69,6 +46,8 @@ pub struct GameSetup {
     pub game: ActorRef<GameMsg>,
     /// the game's async handle, for graceful shutdown
     pub game_handle: Option<JoinHandle<()>>,
+    /// the latest snapshot of the game, to read from without waiting on the game
+    pub game_state: watch::Receiver<GameSnapshot>,
     /// the egui frame
     pub frame: Frame,
     /// the camera
@@ -102,13 +81,24 @@ impl GameSetup {
 
         log::info!("loaded resources.");
 
+        log::info!("loading options...");
+        let options = Options::load()?;
+        log::info!("loaded options.");
+
         // --- game ---
         log::info!("creating game...");
 
+        let (snapshot, game_state) = watch::channel(GameSnapshot::default());
+
         let (game, game_handle) = Actor::spawn(
             Some("game".to_string()),
             Game {
                 resource_man: resource_man.clone(),
+                tick_engine: options.game.tick_engine,
+                save_compression: options.game.save_compression,
+                backup_count: options.game.backup_count,
+                autosave_interval: Duration::from_secs(options.game.autosave_interval),
+                snapshot,
             },
             (),
         )
@@ -123,10 +113,6 @@ impl GameSetup {
 
         log::info!("game created.");
 
-        log::info!("loading options...");
-        let options = Options::load()?;
-        log::info!("loaded options.");
-
         log::info!("loading completed!");
 
         // --- last setup ---
@@ -134,6 +120,8 @@ impl GameSetup {
 
         let camera_coord = camera.get_tile_coord();
 
+        game.send_message(GameMsg::GenerateAround(camera_coord.into()))?;
+
         // --- event-loop ---
         Ok((
             GameSetup {
@@ -141,6 +129,7 @@ impl GameSetup {
                 resource_man,
                 game,
                 game_handle: Some(game_handle),
+                game_state,
                 frame,
                 camera,
                 camera_chunk_coord: camera_coord.into(),
@@ -163,10 +152,7 @@ impl GameSetup {
             .filter(|f| !f.starts_with('.'))
             .flat_map(|map| {
                 Map::read_header(&self.resource_man, &map)
-                    .map(|v| MapInfo {
-                        tile_count: v.0.tile_count,
-                        save_time: v.1,
-                    })
+                    .map(|(header, file_time)| MapInfo::from_header(&header, file_time))
                     .zip(Some(map))
             })
             .collect::<Vec<_>>();
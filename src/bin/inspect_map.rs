@@ -0,0 +1,85 @@
+//! Inspects a saved map without starting the game: decodes its files, checks them against the loaded resources, and prints what it finds.
+//!
+//! Usage: `inspect_map <map name> [--json]`, run from the game's folder.
+
+use std::env;
+use std::path::Path;
+use std::process::ExitCode;
+
+use env_logger::Env;
+use serde::Serialize;
+
+use automancy::inspect::{Problem, Validator};
+use automancy::map::{Map, MapDump};
+use automancy_resources::kira::manager::backend::mock::MockBackend;
+use automancy_resources::kira::manager::{AudioManager, AudioManagerSettings};
+use automancy_resources::kira::track::TrackBuilder;
+use automancy_resources::{ResourceManager, RESOURCES_PATH};
+
+/// Everything printed with `--json`.
+#[derive(Serialize)]
+struct Inspection<'a> {
+    map: &'a MapDump,
+    problems: &'a [Problem],
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    env_logger::Builder::from_env(Env::default().default_filter_or("warn")).init();
+
+    let mut map_name = None;
+    let mut json = false;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => map_name = Some(arg),
+        }
+    }
+
+    let Some(map_name) = map_name else {
+        eprintln!("usage: inspect_map <map name> [--json]");
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let mut audio_man = AudioManager::<MockBackend>::new(AudioManagerSettings::default())?;
+    let track = audio_man.add_sub_track(TrackBuilder::new())?;
+
+    let mut resource_man = ResourceManager::new(track);
+    resource_man.load_all(Path::new(RESOURCES_PATH))?;
+
+    let dump = Map::dump(&map_name)?;
+    let problems = Validator::new(&resource_man, &dump).check(&dump);
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&Inspection {
+                map: &dump,
+                problems: &problems,
+            })?
+        );
+    } else {
+        println!(
+            "map {map_name}, format version {}",
+            dump.header.format_version
+        );
+        println!(
+            "{} of {} tiles read, {} terrain tiles",
+            dump.report.read,
+            dump.report.expected,
+            dump.terrain.len()
+        );
+
+        for problem in &problems {
+            println!("{problem}");
+        }
+
+        println!("{} problems found", problems.len());
+    }
+
+    Ok(if problems.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
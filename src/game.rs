@@ -1,32 +1,30 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::mem;
-use std::ops::Div;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 use arraydeque::{ArrayDeque, Wrapping};
-use ractor::rpc::CallResult;
 use ractor::{Actor, ActorProcessingErr, ActorRef, RpcReplyPort, SupervisionEvent};
-use rayon::prelude::*;
+use tokio::sync::watch;
 
 use automancy_defs::cgmath::vec3;
-use automancy_defs::coord::{TileCoord, TileHex, TileUnit};
-use automancy_defs::hashbrown::HashMap;
-use automancy_defs::hexagon_tiles::traits::HexDirection;
+use automancy_defs::coord::{ChunkCoord, TileCoord, TileUnit};
+use automancy_defs::hashbrown::{HashMap, HashSet};
 use automancy_defs::id::Id;
 use automancy_defs::math::{Float, Matrix4, FAR};
 use automancy_defs::rendering::InstanceData;
 use automancy_defs::{log, math};
 use automancy_resources::data::item::item_match;
-use automancy_resources::data::stack::ItemStack;
+use automancy_resources::data::stack::{ItemAmount, ItemStack};
 use automancy_resources::data::{Data, DataMap};
 use automancy_resources::script::Script;
 use automancy_resources::ResourceManager;
 
 use crate::game::GameMsg::*;
-use crate::map::{Map, MapInfo, TileEntities};
+use crate::map::{Map, MapInfo, TileData, Tiles, MAIN_MENU};
+use crate::simulation::{TickEngine, TileEntities};
 use crate::tile_entity::{TileEntity, TileEntityMsg, TileModifier};
-use crate::util::actor::multi_call_iter;
+use crate::transit::{Shipment, DEFAULT_TRANSIT_CAPACITY, DEFAULT_TRANSIT_TICKS};
 
 /// Miscellaneous updates per second -- e.g. Camera Position.
 pub const UPS: u64 = 60;
@@ -37,7 +35,6 @@ pub const TPS: u64 = 30;
 pub const TICK_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / TPS);
 pub const MAX_ALLOWED_TICK_INTERVAL: Duration = TICK_INTERVAL.saturating_mul(5);
 
-pub const TRANSACTION_ANIMATION_SPEED: Duration = Duration::from_nanos(666_666_666);
 pub const TAKE_ITEM_ANIMATION_SPEED: Duration = Duration::from_nanos(200_000_000);
 
 pub type TickUnit = u16;
@@ -47,6 +44,8 @@ pub struct TransactionRecord {
     pub stack: ItemStack,
     pub source_id: Id,
     pub id: Id,
+    /// how long the item takes to arrive
+    pub duration: Duration,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -56,7 +55,102 @@ pub struct RenderUnit {
     pub model: Id,
 }
 
-pub type RenderInfo = (HashMap<TileCoord, RenderUnit>, HashMap<TileCoord, DataMap>);
+/// The part of the map the renderer is looking at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderView {
+    pub center: TileCoord,
+    pub culling_range: (TileUnit, TileUnit),
+}
+
+impl RenderView {
+    pub fn contains(&self, coord: TileCoord) -> bool {
+        math::is_in_culling_range(self.center, coord, self.culling_range)
+    }
+}
+
+/// What changed in view since the last snapshot the renderer applied.
+#[derive(Debug)]
+pub struct RenderSnapshot {
+    /// the version the renderer is up to date with, once it applies this snapshot
+    pub version: u64,
+    /// the view the snapshot is for
+    pub view: RenderView,
+    /// the version and view of the snapshot the changes are since. Only a full snapshot applies to anything else
+    pub since: Option<(u64, RenderView)>,
+    /// if set, everything the renderer kept from before is outdated
+    pub full: bool,
+    /// the tiles that changed. None if the tile is gone
+    pub tiles: HashMap<TileCoord, Option<RenderUnit>>,
+    /// the terrain at the positions that changed. None if there is none
+    pub terrain: HashMap<TileCoord, Option<RenderUnit>>,
+    /// the data of the tiles that changed
+    pub data: TileData,
+}
+
+/// Keeps track of which tiles changed, so that the renderer only receives what is new.
+#[derive(Debug, Default)]
+struct RenderTracker {
+    /// bumped every time a tile changes
+    version: u64,
+    /// the version the current map was loaded at. Snapshots from before it are outdated
+    base: u64,
+    /// the tiles that changed, by the version they last changed at
+    changes: BTreeMap<u64, TileCoord>,
+    /// the version each changed tile last changed at
+    versions: HashMap<TileCoord, u64>,
+}
+
+impl RenderTracker {
+    /// Marks the tile at the given position as changed.
+    fn mark(&mut self, coord: TileCoord) {
+        self.version += 1;
+
+        if let Some(old) = self.versions.insert(coord, self.version) {
+            self.changes.remove(&old);
+        }
+        self.changes.insert(self.version, coord);
+    }
+
+    /// Forgets every change, making every earlier snapshot outdated.
+    fn reset(&mut self) {
+        self.version += 1;
+        self.base = self.version;
+        self.changes.clear();
+        self.versions.clear();
+    }
+
+    /// Gets the positions of the tiles that changed after the given version.
+    fn changed_since(&self, version: u64) -> impl Iterator<Item = TileCoord> + '_ {
+        self.changes.range(version + 1..).map(|(_, coord)| *coord)
+    }
+}
+
+/// A read-only copy of what the GUI needs from the game, so that it can read it without waiting on the game.
+#[derive(Debug, Clone, Default)]
+pub struct GameSnapshot {
+    /// the name of the map
+    pub map_name: String,
+    /// the tiles on the map
+    pub tiles: Arc<Tiles>,
+    /// the tile that takes up each position other than its own, for tiles bigger than one position
+    pub footprints: Arc<HashMap<TileCoord, TileCoord>>,
+    /// the data of the map
+    pub data: DataMap,
+    /// the data of the watched tile entities, by the positions of the tiles they belong to
+    pub tile_data: TileData,
+    /// what changed in the renderer's view, once it said what it is looking at
+    pub render: Option<Arc<RenderSnapshot>>,
+    /// records transactions to be drawn
+    pub transaction_records: Arc<Mutex<TransactionRecords>>,
+}
+
+impl GameSnapshot {
+    /// Gets the position of the tile that takes up the given position.
+    pub fn owner(&self, coord: TileCoord) -> TileCoord {
+        self.footprints.get(&coord).cloned().unwrap_or(coord)
+    }
+}
+
 pub type TransactionRecords =
     HashMap<(TileCoord, TileCoord), VecDeque<(Instant, TransactionRecord)>>;
 
@@ -69,8 +163,26 @@ pub struct GameState {
 
     /// the tile entities
     tile_entities: TileEntities,
+    /// the tile entities that reported being idle, and are not ticked until woken up
+    sleeping: HashSet<TileCoord>,
     /// the map
     map: Map,
+    /// the tile that takes up each position other than its own, for tiles bigger than one position
+    footprints: HashMap<TileCoord, TileCoord>,
+    /// the chunk the camera was last in, around which the world is generated
+    camera_chunk: Option<ChunkCoord>,
+    /// what changed since the renderer last looked
+    render: RenderTracker,
+    /// the tile entities whose data is published in the snapshot
+    watched: Vec<TileCoord>,
+    /// the tiles and footprints last published in the snapshot. None if the map changed since
+    published_tiles: Option<(Arc<Tiles>, Arc<HashMap<TileCoord, TileCoord>>)>,
+    /// the view the renderer is looking at, and the version and view of the snapshot it last applied
+    render_view: Option<(RenderView, Option<(u64, RenderView)>)>,
+    /// the version of the tiles the snapshot was last published at
+    published_version: u64,
+    /// if set, the snapshot is outdated even if no tile in it changed
+    publish_due: bool,
 
     /// what to do to undo the last UNDO_CACHE_SIZE user events
     undo_steps: ArrayDeque<Vec<GameMsg>, 16, Wrapping>,
@@ -84,6 +196,8 @@ pub enum GameMsg {
     /// tick the tile once
     Tick,
     StopTicking,
+    /// save the map, unless it is the main menu
+    Autosave,
     /// send a message to a tile entity
     ForwardMsgToTile(TileCoord, TileEntityMsg),
     /// place a tile at the given position
@@ -105,9 +219,13 @@ pub enum GameMsg {
 
     /// load a map
     LoadMap(Arc<ResourceManager>, String),
+    /// create a map that the world generator fills with the given seed, with the given description. Loads the map instead if it already exists
+    CreateMap(Arc<ResourceManager>, String, u64, String),
+    /// generate the chunks around the given chunk that are not generated yet
+    GenerateAround(ChunkCoord),
     /// take the map
     TakeMap(RpcReplyPort<Map>),
-    SaveMap(Arc<ResourceManager>, RpcReplyPort<()>),
+    SaveMap(Arc<ResourceManager>, Option<RpcReplyPort<()>>),
     GetMapInfo(RpcReplyPort<(MapInfo, String)>),
 
     /// get the tile at the given position
@@ -120,16 +238,38 @@ pub enum GameMsg {
     GetDataValue(Id, RpcReplyPort<Option<Data>>),
     SetData(Id, Data),
     RemoveData(Id),
-
-    /// get rendering information
-    RenderInfoRequest {
-        center: TileCoord,
-        culling_range: (TileUnit, TileUnit),
-        reply: RpcReplyPort<RenderInfo>,
+    /// add items to the inventory under the key in the map's data
+    AddItem {
+        key: Id,
+        item: Id,
+        amount: ItemAmount,
     },
 
-    GetRecordedTransactions(RpcReplyPort<Arc<Mutex<TransactionRecords>>>),
-    RecordTransaction(ItemStack, TileCoord, TileCoord),
+    /// the renderer is looking at the given view, and last applied the snapshot of the given version and view
+    SetRenderView {
+        view: RenderView,
+        last: Option<(u64, RenderView)>,
+    },
+    /// the data of the tile at the given position changed
+    TileDataChanged(TileCoord),
+    /// publish the data of the tile entities at the given positions in the snapshot
+    WatchTiles(Vec<TileCoord>),
+
+    /// send an item from a tile entity to another, taking as many ticks as the trip takes
+    SendItem {
+        from: TileCoord,
+        to: TileCoord,
+        shipment: Shipment,
+    },
+    /// tell the tile at the given position how many of the items it sent still wait on a result
+    CountInFlight(TileCoord),
+    /// the tile at the given position got the result of one of the items it sent
+    TransactionSettled(TileCoord),
+
+    /// stop ticking the tile until something wakes it up
+    SleepTile(TileCoord),
+    /// resume ticking the tile
+    WakeTile(TileCoord),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -141,6 +281,225 @@ pub enum PlaceTileResponse {
 
 pub struct Game {
     pub resource_man: Arc<ResourceManager>,
+    /// the backend that runs the tile entities
+    pub tick_engine: TickEngine,
+    /// the zstd level maps are saved with
+    pub save_compression: i32,
+    /// how many backups to keep of each map. A map is backed up when it is saved, at most once per autosave interval
+    pub backup_count: usize,
+    /// how often the map is saved on its own. Zero turns autosaving off
+    pub autosave_interval: Duration,
+    /// where the game publishes its snapshots
+    pub snapshot: watch::Sender<GameSnapshot>,
+}
+
+impl Game {
+    /// Saves the map, along with the data of its tile entities.
+    async fn save_map(&self, resource_man: &ResourceManager, state: &mut GameState) {
+        // the files of a map that could not be read are kept as they are, so that they can still be recovered
+        if Map::is_quarantined(&state.map.map_name) {
+            log::warn!(
+                "Not saving map {}, which holds files that could not be read",
+                state.map.map_name
+            );
+
+            return;
+        }
+
+        let tile_data = state.tile_entities.all_data().await;
+
+        state.map.save_time = Some(SystemTime::now());
+
+        if let Err(e) = state.map.save(
+            &resource_man.interner,
+            &tile_data,
+            self.save_compression,
+            self.backup_count,
+            self.autosave_interval,
+        ) {
+            log::error!("Could not save map {}: {e:?}", state.map.map_name);
+
+            resource_man.error_man.push(
+                (
+                    resource_man.registry.err_ids.unwritable_map,
+                    vec![state.map.map_name.clone(), e.to_string()],
+                ),
+                resource_man,
+            );
+
+            return;
+        }
+
+        log::info!("Saved map {}", state.map.map_name.clone());
+    }
+
+    /// Makes what the renderer needs to draw the given tile.
+    fn render_unit(
+        &self,
+        coord: TileCoord,
+        id: Id,
+        tile_modifier: TileModifier,
+    ) -> Option<RenderUnit> {
+        let model = self
+            .resource_man
+            .registry
+            .tile(id)
+            .and_then(|r| r.models.get(tile_modifier as usize).cloned())
+            .map(|id| self.resource_man.get_model(id))?;
+
+        Some(self.render_unit_with_model(coord, id, model))
+    }
+
+    /// Makes what the renderer needs to draw the given model as the given tile.
+    fn render_unit_with_model(&self, coord: TileCoord, id: Id, model: Id) -> RenderUnit {
+        let p = math::hex_to_pixel(coord.into());
+
+        RenderUnit {
+            instance: InstanceData::default().with_model_matrix(Matrix4::from_translation(vec3(
+                p.x as Float,
+                p.y as Float,
+                FAR as Float,
+            ))),
+            tile: id,
+            model,
+        }
+    }
+
+    /// Gets how many ticks items take to leave the given tile, and how many can be on their way to each neighbor.
+    fn transit_params(&self, id: Id) -> (TickUnit, usize) {
+        let registry = &self.resource_man.registry;
+
+        let ticks = registry
+            .tile_data(id, registry.data_ids.transit_ticks)
+            .and_then(Data::as_amount)
+            .map(|ticks| *ticks as TickUnit)
+            .unwrap_or(DEFAULT_TRANSIT_TICKS);
+        let capacity = registry
+            .tile_data(id, registry.data_ids.transit_capacity)
+            .and_then(Data::as_amount)
+            .map(|capacity| *capacity as usize)
+            .unwrap_or(DEFAULT_TRANSIT_CAPACITY);
+
+        (ticks, capacity)
+    }
+
+    /// Gets what changed in the given view since the snapshot of the given version and view, or everything in view if there is no such snapshot.
+    async fn render_snapshot(
+        &self,
+        state: &mut GameState,
+        view: RenderView,
+        last: Option<(u64, RenderView)>,
+    ) -> RenderSnapshot {
+        let last = last.filter(|(version, _)| *version >= state.render.base);
+
+        let mut changed = match last {
+            Some((version, _)) => state
+                .render
+                .changed_since(version)
+                .filter(|coord| view.contains(*coord))
+                .collect::<HashSet<_>>(),
+            None => HashSet::new(),
+        };
+
+        if last.map(|(_, last_view)| last_view) != Some(view) {
+            changed.extend(
+                state
+                    .map
+                    .tiles
+                    .keys()
+                    .chain(state.map.terrain.keys())
+                    .chain(state.map.unknown.keys())
+                    .copied()
+                    .filter(|coord| {
+                        view.contains(*coord)
+                            && !last.is_some_and(|(_, last_view)| last_view.contains(*coord))
+                    }),
+            );
+        }
+
+        let tiles = changed
+            .iter()
+            .map(|coord| {
+                (
+                    *coord,
+                    match state.map.tiles.get(coord) {
+                        Some((id, tile_modifier)) => self.render_unit(*coord, *id, *tile_modifier),
+                        // tiles whose resources are not loaded show that they are missing
+                        None => state.map.unknown.get(coord).map(|_| {
+                            self.render_unit_with_model(
+                                *coord,
+                                self.resource_man.registry.none,
+                                self.resource_man.registry.model_ids.missing,
+                            )
+                        }),
+                    },
+                )
+            })
+            .collect();
+
+        let terrain = changed
+            .iter()
+            .map(|coord| {
+                (
+                    *coord,
+                    state
+                        .map
+                        .terrain
+                        .get(coord)
+                        .and_then(|id| self.render_unit(*coord, *id, 0)),
+                )
+            })
+            .collect();
+
+        let data = state.tile_entities.data(changed).await;
+
+        RenderSnapshot {
+            version: state.render.version,
+            view,
+            since: last,
+            full: last.is_none(),
+            tiles,
+            terrain,
+            data,
+        }
+    }
+
+    /// Publishes a new snapshot of the game.
+    async fn publish(&self, state: &mut GameState) {
+        let (tiles, footprints) = state
+            .published_tiles
+            .get_or_insert_with(|| {
+                (
+                    Arc::new(state.map.tiles.clone()),
+                    Arc::new(state.footprints.clone()),
+                )
+            })
+            .clone();
+        let watched = state
+            .watched
+            .iter()
+            .map(|coord| owner(state, *coord))
+            .collect::<Vec<_>>();
+        let tile_data = state.tile_entities.data(watched).await;
+
+        let render = match state.render_view {
+            Some((view, last)) => Some(Arc::new(self.render_snapshot(state, view, last).await)),
+            None => None,
+        };
+
+        state.published_version = state.render.version;
+        state.publish_due = false;
+
+        self.snapshot.send_replace(GameSnapshot {
+            map_name: state.map.map_name.clone(),
+            tiles,
+            footprints,
+            data: state.map.data.clone(),
+            tile_data,
+            render,
+            transaction_records: state.transaction_records.clone(),
+        });
+    }
 }
 
 #[async_trait::async_trait]
@@ -151,10 +510,17 @@ impl Actor for Game {
 
     async fn pre_start(
         &self,
-        _myself: ActorRef<Self::Msg>,
+        myself: ActorRef<Self::Msg>,
         _args: Self::Arguments,
     ) -> Result<Self::State, ActorProcessingErr> {
-        Ok(Self::State::default())
+        if !self.autosave_interval.is_zero() {
+            myself.send_interval(self.autosave_interval, || Autosave);
+        }
+
+        Ok(Self::State {
+            tile_entities: TileEntities::new(self.tick_engine),
+            ..Default::default()
+        })
     }
 
     async fn handle(
@@ -170,42 +536,51 @@ impl Actor for Game {
                 reply
                     .send(mem::replace(&mut state.map, Map::new_empty(map_name)))
                     .unwrap();
+                state.published_tiles = None;
 
                 return Ok(());
             }
             LoadMap(resource_man, name) => {
-                for tile_entity in state.tile_entities.values() {
-                    tile_entity.stop(Some("Loading new map".to_string()));
-                }
-
-                let (map, tile_entities) = Map::load(myself.clone(), resource_man, &name).await;
+                let (map, tile_data) = Map::load(&resource_man, &name);
 
-                state.map = map;
-                state.tile_entities = tile_entities;
-                state.transaction_records.lock().unwrap().clear();
-                state.undo_steps.clear();
+                switch_map(&myself, state, resource_man, map, tile_data).await;
 
                 log::info!("Successfully loaded map {name}!");
                 return Ok(());
             }
+            CreateMap(resource_man, name, seed, description) => {
+                let (map, tile_data) = if Map::header(&name).exists() {
+                    Map::load(&resource_man, &name)
+                } else {
+                    let mut map = Map::new_generated(name.clone(), seed);
+                    map.description = description;
+
+                    (map, Default::default())
+                };
+
+                switch_map(&myself, state, resource_man, map, tile_data).await;
+
+                log::info!("Successfully created map {name}!");
+                return Ok(());
+            }
+            GenerateAround(chunk) => {
+                state.camera_chunk = Some(chunk);
+
+                generate_around(&self.resource_man, &myself, state, chunk);
+
+                return Ok(());
+            }
             SaveMap(resource_man, reply) => {
-                state
-                    .map
-                    .save(&resource_man.interner, &state.tile_entities)
-                    .await;
-                log::info!("Saved map {}", state.map.map_name.clone());
-                reply.send(()).unwrap();
+                self.save_map(&resource_man, state).await;
+
+                if let Some(reply) = reply {
+                    reply.send(()).unwrap();
+                }
             }
             GetMapInfo(reply) => {
-                let tile_count = state.map.tiles.len() as u64;
-                let save_time = state.map.save_time;
-
                 reply
                     .send((
-                        MapInfo {
-                            tile_count,
-                            save_time,
-                        },
+                        state.map.info(&self.resource_man.interner),
                         state.map.map_name.clone(),
                     ))
                     .unwrap();
@@ -214,11 +589,27 @@ impl Actor for Game {
             }
             TakeDataMap(reply) => {
                 reply.send(mem::take(&mut state.map.data)).unwrap();
+                state.publish_due = true;
 
                 return Ok(());
             }
             SetDataMap(data) => {
                 state.map.data = data;
+                state.publish_due = true;
+            }
+            AddItem { key, item, amount } => {
+                if let Some(inventory) = state
+                    .map
+                    .data
+                    .entry(key)
+                    .or_insert_with(|| Data::Inventory(Default::default()))
+                    .as_inventory_mut()
+                {
+                    inventory.add(item, amount);
+                }
+                state.publish_due = true;
+
+                return Ok(());
             }
             GetDataValue(key, reply) => {
                 reply.send(state.map.data.get(&key).cloned()).unwrap();
@@ -232,67 +623,43 @@ impl Actor for Game {
 
                 match rest {
                     Tick => {
-                        tick(state);
+                        dispatch(&myself, tick(state));
+
+                        for (coord, shipment) in state.map.transit.tick() {
+                            let coord = owner(state, coord);
+
+                            dispatch(
+                                &myself,
+                                state.tile_entities.send(coord, shipment.into_transaction()),
+                            );
+                        }
+
+                        forget_finished_transactions(state);
+
+                        if publish_due(state) {
+                            self.publish(state).await;
+                        }
                     }
                     SetData(key, value) => {
                         state.map.data.insert(key, value);
+                        state.publish_due = true;
                     }
                     RemoveData(key) => {
                         state.map.data.remove(&key);
+                        state.publish_due = true;
                     }
-                    RenderInfoRequest {
-                        center,
-                        culling_range,
-                        reply,
-                    } => {
-                        let instances = state
-                            .map
-                            .tiles
-                            .iter()
-                            .filter(|(coord, _)| {
-                                math::is_in_culling_range(center, **coord, culling_range)
-                            })
-                            .flat_map(|(coord, (id, tile_modifier))| {
-                                self.resource_man
-                                    .registry
-                                    .tile(*id)
-                                    .and_then(|r| r.models.get(*tile_modifier as usize).cloned())
-                                    .map(|id| self.resource_man.get_model(id))
-                                    .map(|model| {
-                                        let p = math::hex_to_pixel((*coord).into());
-
-                                        (
-                                            *coord,
-                                            RenderUnit {
-                                                instance: InstanceData::default()
-                                                    .with_model_matrix(Matrix4::from_translation(
-                                                        vec3(
-                                                            p.x as Float,
-                                                            p.y as Float,
-                                                            FAR as Float,
-                                                        ),
-                                                    )),
-                                                tile: *id,
-                                                model,
-                                            },
-                                        )
-                                    })
-                            })
-                            .collect();
-
-                        let all_data = multi_call_iter(
-                            state.tile_entities.values(),
-                            state.tile_entities.len(),
-                            TileEntityMsg::GetDataWithCoord,
-                            None,
-                        )
-                        .await
-                        .unwrap()
-                        .into_iter()
-                        .map(CallResult::unwrap)
-                        .collect();
-
-                        reply.send((instances, all_data)).unwrap();
+                    SetRenderView { view, last } => {
+                        state.render_view = Some((view, last));
+
+                        self.publish(state).await;
+                    }
+                    TileDataChanged(coord) => {
+                        state.render.mark(coord);
+                    }
+                    WatchTiles(coords) => {
+                        state.watched = coords;
+
+                        self.publish(state).await;
                     }
                     PlaceTile {
                         coord,
@@ -301,6 +668,13 @@ impl Actor for Game {
                         record,
                         reply,
                     } => {
+                        // removing any part of a tile removes all of it
+                        let coord = if id == self.resource_man.registry.none {
+                            owner(state, coord)
+                        } else {
+                            coord
+                        };
+
                         if let Some((old_id, old_tile_modifier)) = state.map.tiles.get(&coord) {
                             if *old_tile_modifier == tile_modifier && *old_id == id {
                                 if let Some(reply) = reply {
@@ -312,6 +686,19 @@ impl Actor for Game {
                         }
 
                         let old_tile = if id == self.resource_man.registry.none {
+                            // a tile whose resources are not loaded cannot be put back, so removing it is not recorded
+                            if !state.map.tiles.contains_key(&coord)
+                                && state.map.unknown.remove(&coord).is_some()
+                            {
+                                state.render.mark(coord);
+
+                                if let Some(reply) = reply {
+                                    reply.send(PlaceTileResponse::Removed).unwrap();
+                                }
+
+                                return Ok(());
+                            }
+
                             if !state.map.tiles.contains_key(&coord) {
                                 if let Some(reply) = reply {
                                     reply.send(PlaceTileResponse::Ignored).unwrap();
@@ -324,8 +711,16 @@ impl Actor for Game {
                                 reply.send(PlaceTileResponse::Removed).unwrap();
                             }
 
-                            remove_tile(state, coord)
+                            remove_tile(&myself, state, coord)
                         } else {
+                            if is_blocked(&self.resource_man, state, coord, id, &HashSet::new()) {
+                                if let Some(reply) = reply {
+                                    reply.send(PlaceTileResponse::Ignored).unwrap();
+                                }
+
+                                return Ok(());
+                            }
+
                             if let Some(reply) = reply {
                                 reply.send(PlaceTileResponse::Placed).unwrap();
                             }
@@ -359,45 +754,107 @@ impl Actor for Game {
                     }
                     GetTileEntity(coord, reply) => {
                         reply
-                            .send(state.tile_entities.get(&coord).cloned())
+                            .send(state.tile_entities.actor(myself.clone(), coord).await)
                             .unwrap();
                     }
                     ForwardMsgToTile(coord, msg) => {
-                        if let Some(tile_entity) = state.tile_entities.get(&coord) {
-                            tile_entity.send_message(msg).unwrap();
-                        }
+                        let coord = owner(state, coord);
+
+                        dispatch(&myself, state.tile_entities.send(coord, msg));
                     }
                     CheckAdjacent {
                         script,
                         coord,
                         self_coord,
                     } => {
-                        if let Some(adjacent) = script.adjacent {
-                            let mut fulfilled = false;
-
-                            for neighbor in TileHex::NEIGHBORS.iter().map(|v| coord + (*v).into()) {
-                                if let Some((id, _)) = state.map.tiles.get(&neighbor) {
-                                    if item_match(&self.resource_man, *id, adjacent) {
-                                        fulfilled = true;
-                                        break;
-                                    }
-                                }
+                        // the positions the tile takes up, which never count as its own neighbors
+                        let own = state
+                            .map
+                            .tiles
+                            .get(&coord)
+                            .map(|(id, _)| footprint(&self.resource_man, coord, *id))
+                            .unwrap_or_default()
+                            .into_iter()
+                            .chain([coord])
+                            .collect::<HashSet<_>>();
+                        let around = own
+                            .iter()
+                            .flat_map(|cell| cell.neighbors())
+                            .filter(|cell| !own.contains(cell))
+                            .collect::<HashSet<_>>();
+
+                        // the first requirement that is not met, if any
+                        let unmet = script.adjacent.iter().position(|adjacency| {
+                            // a map without a world generator has no terrain, so what would need it is not held back by it
+                            if state.map.seed.is_none()
+                                && adjacency.direction == Some(TileCoord::ZERO)
+                            {
+                                return false;
                             }
 
-                            if let Some(entity) = state.tile_entities.get(&self_coord) {
-                                entity
-                                    .send_message(TileEntityMsg::AdjacentState { fulfilled })
-                                    .unwrap();
-                            }
-                        } else if let Some(entity) = state.tile_entities.get(&self_coord) {
-                            entity
-                                .send_message(TileEntityMsg::AdjacentState { fulfilled: true })
-                                .unwrap();
-                        }
+                            let matches = |id: Id| {
+                                adjacency
+                                    .any_of
+                                    .iter()
+                                    .any(|other| item_match(&self.resource_man, id, *other))
+                            };
+
+                            // the positions of the tile itself only count when asked for, and only their terrain
+                            let positions = match adjacency.direction {
+                                Some(direction) if direction == TileCoord::ZERO => {
+                                    own.iter().cloned().collect::<Vec<_>>()
+                                }
+                                Some(direction) => own
+                                    .iter()
+                                    .map(|cell| *cell + direction)
+                                    .filter(|cell| !own.contains(cell))
+                                    .collect(),
+                                None => around.iter().cloned().collect(),
+                            };
+
+                            let matching = positions
+                                .into_iter()
+                                .filter(|position| {
+                                    (!own.contains(position)
+                                        && state
+                                            .map
+                                            .tiles
+                                            .get(&owner(state, *position))
+                                            .is_some_and(|(id, _)| matches(*id)))
+                                        || state
+                                            .map
+                                            .terrain
+                                            .get(position)
+                                            .is_some_and(|id| matches(*id))
+                                })
+                                .count();
+
+                            !adjacency.fulfilled(matching)
+                        });
+
+                        dispatch(
+                            &myself,
+                            state
+                                .tile_entities
+                                .send(self_coord, TileEntityMsg::AdjacentState { unmet }),
+                        );
                     }
                     StopTicking => {
                         state.stopped = true;
                     }
+                    Autosave => {
+                        if state.map.map_name != MAIN_MENU {
+                            self.save_map(&self.resource_man, state).await;
+                        }
+                    }
+                    SleepTile(coord) => {
+                        if state.tile_entities.contains(coord) {
+                            state.sleeping.insert(coord);
+                        }
+                    }
+                    WakeTile(coord) => {
+                        state.sleeping.remove(&coord);
+                    }
                     Undo => {
                         if let Some(step) = state.undo_steps.pop_back() {
                             for msg in step {
@@ -405,89 +862,150 @@ impl Actor for Game {
                             }
                         }
                     }
-                    GetRecordedTransactions(reply) => {
-                        let mut transaction_records = state.transaction_records.lock().unwrap();
-                        let mut to_remove = HashMap::new();
-
-                        let now = Instant::now();
-                        for (coord, deque) in transaction_records.iter() {
-                            to_remove.insert(
-                                *coord,
-                                deque
-                                    .iter()
-                                    .take_while(|(instant, _)| {
-                                        now.duration_since(*instant) >= TRANSACTION_ANIMATION_SPEED
-                                    })
-                                    .count(),
-                            );
+                    SendItem { from, to, shipment } => {
+                        // an item the tile sends itself gets a result, whether the edge takes it or not
+                        if from == shipment.root_coord {
+                            state.map.transit.sent(from);
                         }
 
-                        for (coord, v) in to_remove {
-                            for _ in 0..v {
-                                transaction_records.get_mut(&coord).unwrap().pop_front();
+                        let (ticks, capacity) = state
+                            .map
+                            .tiles
+                            .get(&from)
+                            .map(|(id, _)| self.transit_params(*id))
+                            .unwrap_or((DEFAULT_TRANSIT_TICKS, DEFAULT_TRANSIT_CAPACITY));
+
+                        match state.map.transit.push(from, to, shipment, ticks, capacity) {
+                            Ok(()) => {
+                                if let Some(((source_id, _), (id, _))) = state
+                                    .map
+                                    .tiles
+                                    .get(&from)
+                                    .cloned()
+                                    .zip(state.map.tiles.get(&owner(state, to)).cloned())
+                                {
+                                    state
+                                        .transaction_records
+                                        .lock()
+                                        .unwrap()
+                                        .entry((from, to))
+                                        .or_insert_with(Default::default)
+                                        .push_back((
+                                            Instant::now(),
+                                            TransactionRecord {
+                                                stack: shipment.stack,
+                                                source_id,
+                                                id,
+                                                duration: TICK_INTERVAL * ticks as u32,
+                                            },
+                                        ));
+                                }
                             }
-                        }
-
-                        reply.send(state.transaction_records.clone()).unwrap();
-                    }
-                    RecordTransaction(stack, source_coord, coord) => {
-                        let mut transaction_records = state.transaction_records.lock().unwrap();
-
-                        if let Some((instant, _)) = transaction_records
-                            .get(&(source_coord, coord))
-                            .and_then(|v| v.back())
-                        {
-                            if Instant::now().duration_since(*instant)
-                                < TRANSACTION_ANIMATION_SPEED.div(4)
-                            {
-                                return Ok(());
+                            Err(shipment) => {
+                                dispatch(
+                                    &myself,
+                                    state.tile_entities.send(
+                                        shipment.root_coord,
+                                        TileEntityMsg::TransactionResult { result: Err(()) },
+                                    ),
+                                );
                             }
                         }
+                    }
+                    TransactionSettled(coord) => {
+                        state.map.transit.settled(coord);
+                    }
+                    CountInFlight(coord) => {
+                        let count = state.map.transit.in_flight(coord);
 
-                        if let Some(((source_id, _), (id, _))) = state
-                            .map
-                            .tiles
-                            .get(&source_coord)
-                            .cloned()
-                            .zip(state.map.tiles.get(&coord).cloned())
-                        {
-                            transaction_records
-                                .entry((source_coord, coord))
-                                .or_insert_with(Default::default)
-                                .push_back((
-                                    Instant::now(),
-                                    TransactionRecord {
-                                        stack,
-                                        source_id,
-                                        id,
-                                    },
-                                ));
-                        }
+                        dispatch(
+                            &myself,
+                            state
+                                .tile_entities
+                                .send(coord, TileEntityMsg::InFlight(count)),
+                        );
                     }
                     MoveTiles(tiles, direction, record) => {
                         let mut undo = vec![];
 
-                        tiles
+                        let leaving = tiles
                             .into_iter()
-                            .flat_map(|coord| {
-                                Some(coord).zip(
-                                    state
-                                        .map
-                                        .tiles
-                                        .remove(&coord)
-                                        .zip(state.tile_entities.remove(&coord)),
-                                )
+                            .filter(|coord| {
+                                state.tile_entities.contains(*coord)
+                                    && state.map.tiles.contains_key(coord)
                             })
-                            .collect::<Vec<_>>()
+                            .collect::<HashSet<_>>();
+
+                        // the tiles move together or not at all
+                        let blocked = leaving.iter().any(|coord| {
+                            let new_coord = *coord + direction;
+                            let (id, _) = state.map.tiles[coord];
+
+                            (state.map.tiles.contains_key(&new_coord)
+                                && !leaving.contains(&new_coord))
+                                || is_blocked(&self.resource_man, state, new_coord, id, &leaving)
+                        });
+
+                        if blocked {
+                            return Ok(());
+                        }
+
+                        let moved = leaving
                             .into_iter()
-                            .for_each(|(coord, ((id, modifier), tile_entity))| {
-                                let new_coord = coord + direction;
+                            .flat_map(|coord| Some(coord).zip(state.map.tiles.remove(&coord)))
+                            .collect::<Vec<_>>();
+
+                        let unknown_data = moved
+                            .iter()
+                            .flat_map(|(coord, _)| {
+                                Some(*coord + direction)
+                                    .zip(state.map.unknown_tile_data.remove(coord))
+                            })
+                            .collect::<Vec<_>>();
+                        state.map.unknown_tile_data.extend(unknown_data);
+
+                        state.tile_entities.move_all(
+                            &moved
+                                .iter()
+                                .map(|(coord, _)| (*coord, *coord + direction))
+                                .collect::<Vec<_>>(),
+                        );
+
+                        for (coord, _) in &moved {
+                            for cell in vacate(state, *coord) {
+                                notify_neighbors(&myself, state, cell);
+                            }
+                        }
+
+                        for (coord, (id, modifier)) in moved {
+                            let new_coord = coord + direction;
+
+                            state.map.tiles.insert(new_coord, (id, modifier));
+                            occupy(
+                                &myself,
+                                state,
+                                new_coord,
+                                &footprint(&self.resource_man, new_coord, id),
+                            );
 
-                                state.map.tiles.insert(new_coord, (id, modifier));
-                                state.tile_entities.insert(new_coord, tile_entity);
+                            state.sleeping.remove(&coord);
+                            state.sleeping.remove(&new_coord);
+                            state.render.mark(coord);
+                            state.render.mark(new_coord);
+                            state.published_tiles = None;
+                            notify_neighbors(&myself, state, coord);
+                            notify_neighbors(&myself, state, new_coord);
+                            // the tile is on other terrain now, which also makes it check its neighbors
+                            let terrain = state.map.terrain.get(&new_coord).cloned();
+                            dispatch(
+                                &myself,
+                                state
+                                    .tile_entities
+                                    .send(new_coord, TileEntityMsg::Terrain(terrain)),
+                            );
 
-                                undo.push(new_coord);
-                            });
+                            undo.push(new_coord);
+                        }
 
                         if record {
                             state
@@ -527,13 +1045,256 @@ impl Actor for Game {
     }
 }
 
+/// Checks if the snapshot is outdated: if something besides the tiles changed, or a tile that is watched or in the renderer's view did.
+fn publish_due(state: &GameState) -> bool {
+    if state.publish_due || state.published_tiles.is_none() {
+        return true;
+    }
+
+    let watched = state
+        .watched
+        .iter()
+        .map(|coord| owner(state, *coord))
+        .collect::<HashSet<_>>();
+
+    state
+        .render
+        .changed_since(state.published_version)
+        .any(|coord| {
+            watched.contains(&coord)
+                || state
+                    .render_view
+                    .is_some_and(|(view, _)| view.contains(coord))
+        })
+}
+
+/// Forgets the recorded transactions that have finished being drawn.
+fn forget_finished_transactions(state: &mut GameState) {
+    let now = Instant::now();
+
+    for records in state.transaction_records.lock().unwrap().values_mut() {
+        while records
+            .front()
+            .is_some_and(|(instant, record)| now.duration_since(*instant) >= record.duration)
+        {
+            records.pop_front();
+        }
+    }
+}
+
+/// Wakes up all the tiles around the given position and tells them their surroundings have changed.
+fn notify_neighbors(game: &ActorRef<GameMsg>, state: &mut GameState, coord: TileCoord) {
+    for neighbor in coord.neighbors() {
+        state.sleeping.remove(&neighbor);
+
+        dispatch(
+            game,
+            state
+                .tile_entities
+                .send(neighbor, TileEntityMsg::NeighborChanged),
+        );
+    }
+}
+
+/// Replaces the current map, and creates the tile entities of the new one.
+async fn switch_map(
+    game: &ActorRef<GameMsg>,
+    state: &mut GameState,
+    resource_man: Arc<ResourceManager>,
+    map: Map,
+    mut tile_data: TileData,
+) {
+    state.tile_entities.clear();
+
+    for (coord, (id, tile_modifier)) in map.tiles.iter() {
+        state
+            .tile_entities
+            .insert(
+                resource_man.clone(),
+                game.clone(),
+                *coord,
+                *id,
+                *tile_modifier,
+                tile_data.remove(coord).unwrap_or_default(),
+            )
+            .await;
+    }
+
+    state.footprints.clear();
+    for (coord, (id, _)) in map.tiles.iter() {
+        occupy(game, state, *coord, &footprint(&resource_man, *coord, *id));
+
+        if let Some(terrain) = map.terrain.get(coord) {
+            dispatch(
+                game,
+                state
+                    .tile_entities
+                    .send(*coord, TileEntityMsg::Terrain(Some(*terrain))),
+            );
+        }
+    }
+
+    // the tiles still wait on the results of the items they sent before the map was saved
+    for (coord, count) in map.transit.outstanding() {
+        dispatch(
+            game,
+            state
+                .tile_entities
+                .send(coord, TileEntityMsg::InFlight(count)),
+        );
+    }
+
+    state.map = map;
+    state.sleeping.clear();
+    state.render.reset();
+    state.published_tiles = None;
+    state.transaction_records.lock().unwrap().clear();
+    state.undo_steps.clear();
+
+    if let Some(chunk) = state.camera_chunk {
+        generate_around(&resource_man, game, state, chunk);
+    }
+}
+
+/// Fills the chunk and the chunks next to it with the world generator, if the map is generated and they are not filled yet.
+/// Never replaces what is already there.
+fn generate_around(
+    resource_man: &ResourceManager,
+    game: &ActorRef<GameMsg>,
+    state: &mut GameState,
+    chunk: ChunkCoord,
+) {
+    let Some(seed) = state.map.seed else {
+        return;
+    };
+
+    for chunk in [chunk].into_iter().chain(chunk.neighbors()) {
+        if !state.map.generated.insert(chunk) {
+            continue;
+        }
+
+        for (coord, id) in resource_man.registry.worldgen.generate(seed, chunk) {
+            if state.map.terrain.contains_key(&coord) || resource_man.registry.tile(id).is_none() {
+                continue;
+            }
+
+            set_terrain(game, state, coord, id);
+        }
+    }
+}
+
+/// Sets the terrain at the given position, and tells the tile on it and its neighbors.
+fn set_terrain(game: &ActorRef<GameMsg>, state: &mut GameState, coord: TileCoord, id: Id) {
+    state.map.terrain.insert(coord, id);
+    state.render.mark(coord);
+
+    if state.map.tiles.contains_key(&coord) {
+        dispatch(
+            game,
+            state
+                .tile_entities
+                .send(coord, TileEntityMsg::Terrain(Some(id))),
+        );
+    }
+
+    notify_neighbors(game, state, coord);
+}
+
+/// Gets the position of the tile that takes up the given position.
+fn owner(state: &GameState, coord: TileCoord) -> TileCoord {
+    state.footprints.get(&coord).cloned().unwrap_or(coord)
+}
+
+/// Gets the other positions a tile of the given type takes up, if placed at the given position.
+fn footprint(resource_man: &ResourceManager, coord: TileCoord, id: Id) -> Vec<TileCoord> {
+    resource_man
+        .registry
+        .tile(id)
+        .map(|tile| {
+            tile.footprint
+                .iter()
+                .map(|offset| coord + *offset)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Checks if a tile of the given type cannot go at the given position, because another tile, or a tile whose resources are not loaded, is in the way.
+/// A tile at the position itself is replaced rather than in the way, and the tiles at the positions in `leaving` are moving away, so are not in the way either.
+fn is_blocked(
+    resource_man: &ResourceManager,
+    state: &GameState,
+    coord: TileCoord,
+    id: Id,
+    leaving: &HashSet<TileCoord>,
+) -> bool {
+    let in_the_way = |owner: &TileCoord| !leaving.contains(owner);
+
+    state.map.unknown.contains_key(&coord)
+        || state.footprints.get(&coord).is_some_and(in_the_way)
+        || footprint(resource_man, coord, id).iter().any(|cell| {
+            state.map.unknown.contains_key(cell)
+                || (state.map.tiles.contains_key(cell) && in_the_way(cell))
+                || state
+                    .footprints
+                    .get(cell)
+                    .is_some_and(|owner| *owner != coord && in_the_way(owner))
+        })
+}
+
+/// Reserves the other positions of the tile at the given position, and sends their messages to it. The tiles around each of them are told their surroundings have changed.
+fn occupy(game: &ActorRef<GameMsg>, state: &mut GameState, coord: TileCoord, cells: &[TileCoord]) {
+    for cell in cells {
+        state.footprints.insert(*cell, coord);
+        state.tile_entities.alias(*cell, coord);
+    }
+
+    for cell in cells {
+        notify_neighbors(game, state, *cell);
+    }
+}
+
+/// Frees the other positions of the tile at the given position. Returns the positions freed.
+fn vacate(state: &mut GameState, coord: TileCoord) -> Vec<TileCoord> {
+    let cells = state
+        .footprints
+        .iter()
+        .filter(|(_, owner)| **owner == coord)
+        .map(|(cell, _)| *cell)
+        .collect::<Vec<_>>();
+
+    for cell in &cells {
+        state.footprints.remove(cell);
+        state.tile_entities.unalias(*cell);
+    }
+
+    cells
+}
+
+/// Sends the messages the tile entities left for the game back to it.
+fn dispatch(game: &ActorRef<GameMsg>, messages: Vec<GameMsg>) {
+    for message in messages {
+        game.send_message(message).unwrap();
+    }
+}
+
 /// Stops a tile and removes it from the game
-fn remove_tile(state: &mut GameState, coord: TileCoord) -> Option<(Id, TileModifier)> {
-    if let Some(tile_entity) = state.tile_entities.get(&coord) {
-        tile_entity.stop(Some("Removed from game".to_string()));
+fn remove_tile(
+    game: &ActorRef<GameMsg>,
+    state: &mut GameState,
+    coord: TileCoord,
+) -> Option<(Id, TileModifier)> {
+    state.sleeping.remove(&coord);
+    state.render.mark(coord);
+    state.published_tiles = None;
+    notify_neighbors(game, state, coord);
+    for cell in vacate(state, coord) {
+        notify_neighbors(game, state, cell);
     }
 
-    state.tile_entities.remove(&coord);
+    state.tile_entities.remove(coord);
+    state.map.transit.forget(coord);
+    state.map.unknown_tile_data.remove(&coord);
     state.map.tiles.remove(&coord)
 }
 
@@ -571,32 +1332,49 @@ async fn insert_new_tile(
     id: Id,
     tile_modifier: TileModifier,
 ) -> Option<(Id, TileModifier)> {
-    let old = remove_tile(state, coord);
+    let old = remove_tile(&game, state, coord);
+    let cells = footprint(&resource_man, coord, id);
 
-    let tile_entity = new_tile(resource_man, game, coord, id, tile_modifier).await;
-
-    state.tile_entities.insert(coord, tile_entity);
+    state
+        .tile_entities
+        .insert(
+            resource_man,
+            game.clone(),
+            coord,
+            id,
+            tile_modifier,
+            DataMap::default(),
+        )
+        .await;
     state.map.tiles.insert(coord, (id, tile_modifier));
+    occupy(&game, state, coord, &cells);
+
+    if let Some(terrain) = state.map.terrain.get(&coord).cloned() {
+        dispatch(
+            &game,
+            state
+                .tile_entities
+                .send(coord, TileEntityMsg::Terrain(Some(terrain))),
+        );
+    }
 
     old
 }
 
-fn inner_tick(state: &mut GameState) {
-    state.tile_entities.par_iter().for_each(|(_, tile_entity)| {
-        if let Err(e) = tile_entity.send_message(TileEntityMsg::Tick {
-            tick_count: state.tick_count,
-        }) {
-            log::error!("{e:?}");
-        }
-    });
+fn inner_tick(state: &mut GameState) -> Vec<GameMsg> {
+    let messages = state.tile_entities.tick(state.tick_count, &state.sleeping);
 
     state.tick_count = state.tick_count.wrapping_add(1);
+    state.map.tick_count += 1;
+    state.map.play_time += TICK_INTERVAL;
+
+    messages
 }
 
-/// Runs the game for one tick, logging if the tick is too long.
-pub fn tick(state: &mut GameState) {
+/// Runs the game for one tick, logging if the tick is too long. Returns the messages the tile entities left for the game.
+pub fn tick(state: &mut GameState) -> Vec<GameMsg> {
     let start = Instant::now();
-    inner_tick(state);
+    let messages = inner_tick(state);
     let finish = Instant::now();
 
     let tick_time = finish - start;
@@ -608,6 +1386,8 @@ pub fn tick(state: &mut GameState) {
             MAX_ALLOWED_TICK_INTERVAL
         );
     }
+
+    messages
 }
 
 impl Default for GameState {
@@ -617,7 +1397,16 @@ impl Default for GameState {
             stopped: false,
 
             map: Map::new_empty("".to_string()),
-            tile_entities: Default::default(),
+            footprints: Default::default(),
+            camera_chunk: None,
+            render: Default::default(),
+            watched: Default::default(),
+            published_tiles: None,
+            render_view: None,
+            published_version: 0,
+            publish_due: false,
+            tile_entities: TileEntities::new(TickEngine::default()),
+            sleeping: Default::default(),
 
             undo_steps: Default::default(),
             transaction_records: Arc::new(Default::default()),
@@ -462,6 +462,12 @@ pub struct GameResources {
     post_effects_bind_group: Option<BindGroup>,
 }
 
+#[derive(OptionGetter)]
+pub struct TerrainResources {
+    pub instance_buffer: Buffer,
+    pub indirect_buffer: Buffer,
+}
+
 #[derive(OptionGetter)]
 pub struct InWorldItemResources {
     pub instance_buffer: Buffer,
@@ -550,6 +556,7 @@ pub struct Gpu {
     pub non_filtering_sampler: Sampler,
 
     pub game_resources: GameResources,
+    pub terrain_resources: TerrainResources,
     pub in_world_item_resources: InWorldItemResources,
     pub gui_resources: GuiResources,
     pub item_resources: ItemResources,
@@ -803,6 +810,19 @@ impl Gpu {
             }
         };
 
+        let terrain_resources = TerrainResources {
+            instance_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: &[],
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            }),
+            indirect_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: &[],
+                usage: BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+            }),
+        };
+
         let in_world_item_resources = {
             let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
                 label: Some("In-world Item Uniform Buffer"),
@@ -1135,6 +1155,7 @@ impl Gpu {
             non_filtering_sampler,
 
             game_resources,
+            terrain_resources,
             in_world_item_resources,
             gui_resources,
             item_resources,
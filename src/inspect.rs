@@ -0,0 +1,221 @@
+//! Checks saved maps against the loaded resources, for inspecting maps without starting the game.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::Serialize;
+
+use automancy_defs::coord::TileCoord;
+use automancy_defs::id::IdRaw;
+use automancy_resources::data::stack::ItemAmount;
+use automancy_resources::data::{DataMapRaw, DataRaw};
+use automancy_resources::ResourceManager;
+
+use crate::map::{MapDump, TileRaw};
+
+/// Something wrong with a map.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    /// the tile the problem is at, if it is at one
+    pub coord: Option<TileCoord>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.coord {
+            Some(coord) => write!(f, "{coord}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Checks a map against the loaded resources.
+pub struct Validator<'a> {
+    resource_man: &'a ResourceManager,
+    /// every position a tile takes up
+    occupied: HashSet<TileCoord>,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(resource_man: &'a ResourceManager, dump: &MapDump) -> Self {
+        let mut occupied = HashSet::new();
+
+        for (coord, tile) in &dump.tiles {
+            occupied.insert(*coord);
+
+            if let Some(info) = resource_man
+                .interner
+                .get(tile.id.as_str())
+                .and_then(|id| resource_man.registry.tile(id))
+            {
+                occupied.extend(info.footprint.iter().map(|offset| *coord + *offset));
+            }
+        }
+
+        Self {
+            resource_man,
+            occupied,
+            problems: vec![],
+        }
+    }
+
+    fn report(&mut self, coord: Option<TileCoord>, message: String) {
+        self.problems.push(Problem { coord, message });
+    }
+
+    /// Whether the id is loaded at all.
+    fn is_known(&self, id: &IdRaw) -> bool {
+        self.resource_man.interner.get(id.to_string()).is_some()
+    }
+
+    /// Whether the id is a loaded item.
+    fn is_item(&self, id: &IdRaw) -> bool {
+        self.resource_man
+            .interner
+            .get(id.to_string())
+            .and_then(|id| self.resource_man.registry.item(id))
+            .is_some()
+    }
+
+    fn check_tile(&mut self, coord: TileCoord, tile: &TileRaw) {
+        let resource_man = self.resource_man;
+        let registry = &resource_man.registry;
+
+        let Some(info) = resource_man
+            .interner
+            .get(tile.id.as_str())
+            .and_then(|id| registry.tile(id))
+        else {
+            self.report(Some(coord), format!("unknown tile {}", tile.id));
+            self.check_data(Some(coord), &tile.data, None);
+            return;
+        };
+
+        if tile.tile_modifier < 0 || tile.tile_modifier as usize >= info.models.len() {
+            self.report(
+                Some(coord),
+                format!(
+                    "tile modifier {} is out of range, {} only has {} models",
+                    tile.tile_modifier,
+                    tile.id,
+                    info.models.len()
+                ),
+            );
+        }
+
+        let max_amount = info
+            .data
+            .get(&registry.data_ids.max_amount)
+            .and_then(|v| v.as_amount())
+            .cloned();
+
+        self.check_data(Some(coord), &tile.data, max_amount);
+    }
+
+    fn check_data(
+        &mut self,
+        coord: Option<TileCoord>,
+        data: &DataMapRaw,
+        max_amount: Option<ItemAmount>,
+    ) {
+        let resource_man = self.resource_man;
+        let interner = &resource_man.interner;
+        let data_ids = &resource_man.registry.data_ids;
+        let target = interner.resolve(data_ids.target).unwrap_or_default();
+        let byproduct_target = interner
+            .resolve(data_ids.byproduct_target)
+            .unwrap_or_default();
+        let link = interner.resolve(data_ids.link).unwrap_or_default();
+
+        for (key, value) in data.clone().into_inner() {
+            let key = IdRaw::parse(&key);
+            let name = key.to_string();
+
+            if !self.is_known(&key) {
+                self.report(coord, format!("unknown data key {name}"));
+            }
+
+            match value {
+                DataRaw::Inventory(inventory) => {
+                    for (item, amount) in inventory.iter() {
+                        if !self.is_item(item) {
+                            self.report(coord, format!("{name} holds unknown item {item}"));
+                        }
+
+                        if *amount < 0 {
+                            self.report(coord, format!("{name} holds {amount} of {item}"));
+                        } else if let Some(max_amount) = max_amount.filter(|v| amount > v) {
+                            self.report(
+                                coord,
+                                format!("{name} holds {amount} of {item}, more than the {max_amount} the tile can hold"),
+                            );
+                        }
+                    }
+                }
+                DataRaw::VecStack(stacks) => {
+                    for (item, _) in stacks {
+                        if !self.is_item(&item) {
+                            self.report(coord, format!("{name} holds unknown item {item}"));
+                        }
+                    }
+                }
+                DataRaw::Id(id) => {
+                    if !self.is_known(&id) {
+                        self.report(coord, format!("{name} is unknown id {id}"));
+                    }
+                }
+                DataRaw::VecId(ids) => {
+                    for id in ids {
+                        if !self.is_known(&id) {
+                            self.report(coord, format!("{name} has unknown id {id}"));
+                        }
+                    }
+                }
+                DataRaw::Coord(v) => {
+                    let Some(coord) = coord else {
+                        continue;
+                    };
+
+                    let pointing_at = if name == target || name == byproduct_target {
+                        coord + v
+                    } else if name == link {
+                        v
+                    } else {
+                        continue;
+                    };
+
+                    if !self.occupied.contains(&pointing_at) {
+                        self.report(
+                            Some(coord),
+                            format!("{name} points at {pointing_at}, where there is no tile"),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Checks everything in the map, and gets the problems found.
+    pub fn check(mut self, dump: &MapDump) -> Vec<Problem> {
+        for error in &dump.report.errors {
+            self.report(None, error.clone());
+        }
+
+        self.check_data(None, &dump.header.data, None);
+
+        for (coord, tile) in &dump.tiles {
+            self.check_tile(*coord, tile);
+        }
+
+        for (coord, id) in &dump.terrain {
+            if !self.is_known(&IdRaw::parse(id)) {
+                self.report(Some(*coord), format!("unknown terrain {id}"));
+            }
+        }
+
+        self.problems
+    }
+}
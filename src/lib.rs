@@ -3,7 +3,11 @@ pub mod camera;
 pub mod game;
 pub mod gpu;
 pub mod input;
+pub mod inspect;
 pub mod map;
+pub mod migration;
 pub mod options;
+pub mod simulation;
 pub mod tile_entity;
+pub mod transit;
 pub mod util;
@@ -1,41 +1,69 @@
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::iter::Iterator;
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::mem;
+use std::time::{Duration, SystemTime};
 use std::{
     collections::{HashMap, HashSet},
     fs,
-    path::PathBuf,
+    path::{Component, Path, PathBuf},
 };
 
+use anyhow::Context;
 use lazy_static::lazy_static;
-use ractor::ActorRef;
-use serde::{Deserialize, Serialize};
+use rand::random;
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 use zstd::{Decoder, Encoder};
 
-use automancy_defs::coord::TileCoord;
+use automancy_defs::coord::{ChunkCoord, TileCoord};
 use automancy_defs::id::{Id, Interner};
 use automancy_defs::log;
 use automancy_resources::chrono::Local;
+use automancy_resources::data::packed::{DataMapPacked, Key, KeyLookup, KeyTable};
 use automancy_resources::data::{DataMap, DataMapRaw};
 use automancy_resources::ResourceManager;
 
-use crate::game;
-use crate::game::GameMsg;
-use crate::tile_entity::{TileEntityMsg, TileModifier};
+use crate::migration;
+use crate::migration::MigrationError;
+use crate::migration::{
+    CHUNK_KEYS_FORMAT_VERSION, MAP_FORMAT_VERSION, PACKED_FORMAT_VERSION, PRELUDE_FORMAT_VERSION,
+};
+use crate::tile_entity::TileModifier;
+use crate::transit::{ShipmentRaw, Transit};
+use crate::VERSION;
 
 pub const MAP_PATH: &str = "map";
 pub const MAP_EXT: &str = ".zst";
 pub const HEADER_EXT: &str = ".json";
+pub const BACKUP_PATH: &str = "backups";
+pub const TEMP_EXT: &str = ".tmp";
+pub const ARCHIVE_EXT: &str = ".tar.zst";
 
 pub const MAIN_MENU: &str = ".main_menu";
 
+/// What the names of maps whose files were moved aside after they could not be read contain.
+pub const QUARANTINE_MARK: &str = "-ERR-";
+
 const MAP_BUFFER_SIZE: usize = 256 * 1024;
 
 pub type Tiles = HashMap<TileCoord, (Id, TileModifier)>;
-pub type TileEntities = HashMap<TileCoord, ActorRef<TileEntityMsg>>;
+pub type TileData = HashMap<TileCoord, DataMap>;
+pub type Terrain = HashMap<TileCoord, Id>;
+pub type UnknownTiles = HashMap<TileCoord, TileRaw>;
+pub type UnknownTerrain = HashMap<TileCoord, String>;
+pub type UnknownTileData = HashMap<TileCoord, DataMapRaw>;
+
+/// A tile as it was saved, with its ids written out. Tiles whose ids are not loaded, such as ones from a mod that was removed, are kept like this so that they come back once their resources are loaded again.
+#[derive(Debug, Clone, Serialize)]
+pub struct TileRaw {
+    /// The id of the tile, written out.
+    pub id: String,
+    pub tile_modifier: TileModifier,
+    pub data: DataMapRaw,
+}
 
 /// A map stores tiles and tile entities to disk.
 #[derive(Debug, Clone)]
@@ -44,32 +72,253 @@ pub struct Map {
     pub map_name: String,
     /// The list of tiles.
     pub tiles: Tiles,
+    /// The ground under the tiles, such as deposits.
+    pub terrain: Terrain,
+    /// The tiles whose ids are not loaded. They are inert, and saved back as they were.
+    pub unknown: UnknownTiles,
+    /// The terrain whose ids are not loaded, saved back as it was.
+    pub unknown_terrain: UnknownTerrain,
     /// The list of tile data.
     pub data: DataMap,
+    /// The entries and items of the map's data whose ids are not loaded, saved back as they were.
+    pub unknown_data: DataMapRaw,
+    /// The entries and items of the tiles' data whose ids are not loaded, saved back as they were as long as the tile stays.
+    pub unknown_tile_data: UnknownTileData,
+    /// The items on their way between tiles.
+    pub transit: Transit,
     /// The last save time as a UTC Unix timestamp.
     pub save_time: Option<SystemTime>,
+    /// The seed of the world generator. None if the map is not generated.
+    pub seed: Option<u64>,
+    /// The chunks the world generator already filled.
+    pub generated: HashSet<ChunkCoord>,
+    /// When the map was created. None if it was created before this was recorded.
+    pub created: Option<SystemTime>,
+    /// How long the game has run on the map, in game time.
+    pub play_time: Duration,
+    /// How many ticks the game has run on the map.
+    pub tick_count: u64,
+    /// What the player wrote about the map.
+    pub description: String,
 }
 
 /// Contains information about a map.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MapInfo {
     /// The number of saved tiles.
     pub tile_count: u64,
     /// The last save time as a UTC Unix timestamp.
     pub save_time: Option<SystemTime>,
+    /// When the map was created.
+    pub created: Option<SystemTime>,
+    /// How long the game has run on the map, in game time.
+    pub play_time: Duration,
+    /// How many ticks the game has run on the map.
+    pub tick_count: u64,
+    /// The version of the game the map was last saved with. None if it is older than this was recorded.
+    pub version: Option<String>,
+    /// What the player wrote about the map.
+    pub description: String,
+    /// The resource namespaces the tiles on the map come from.
+    pub namespaces: Vec<String>,
+    /// The version of the format the map was saved in.
+    #[serde(default)]
+    pub format_version: u32,
+}
+
+impl MapInfo {
+    /// Gets the information about a map from its header. The save time falls back to the given file time, for maps that did not record it.
+    pub fn from_header(header: &MapHeader, file_time: Option<SystemTime>) -> Self {
+        Self {
+            tile_count: header.tile_count,
+            save_time: header.save_time.or(file_time),
+            created: header.created,
+            play_time: header.play_time,
+            tick_count: header.tick_count,
+            version: header.version.clone(),
+            description: header.description.clone(),
+            namespaces: header.namespaces.clone(),
+            format_version: header.format_version,
+        }
+    }
+
+    /// Checks if the map was saved by a newer version of the game, which this one cannot read.
+    pub fn is_too_new(&self) -> bool {
+        self.format_version > MAP_FORMAT_VERSION
+    }
+
+    /// Gets the namespaces the map uses that are not loaded.
+    pub fn missing_namespaces(&self, resource_man: &ResourceManager) -> Vec<String> {
+        self.namespaces
+            .iter()
+            .filter(|namespace| !resource_man.namespaces.contains(namespace))
+            .cloned()
+            .collect()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SerdeTile(Id, TileModifier, DataMapRaw);
 
+/// A tile as it is saved since format version 2, with its ids replaced by keys.
+/// Its fields are saved in order without names, so changing them needs a new format version.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackedTile(TileCoord, Key, TileModifier, DataMapPacked);
+
+/// The start of the tiles since format version 4, which says how many chunks follow it.
+/// Like [`PackedTile`], its fields are saved in order without names.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackedPrelude {
+    /// the save that wrote the tiles, as recorded in the header
+    pub generation: u64,
+    pub chunk_count: u64,
+}
+
+/// The start of the tiles in format version 3, which has every key before the chunks.
+#[derive(Deserialize)]
+struct KeyedPrelude {
+    generation: u64,
+    keys: Vec<String>,
+    chunk_count: u64,
+}
+
+/// The tiles and terrain in one chunk, which are saved and loaded together.
+/// Like [`PackedTile`], its fields are saved in order without names. Since format version 4, each chunk is saved after the ids of the keys it is the first to use.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PackedChunk {
+    pub tiles: Vec<PackedTile>,
+    pub terrain: Vec<(TileCoord, Key)>,
+}
+
+/// Everything read from a map's files, apart from the header.
+#[derive(Default)]
+struct Contents {
+    tiles: Tiles,
+    tile_data: TileData,
+    terrain: Terrain,
+    unknown: UnknownTiles,
+    unknown_terrain: UnknownTerrain,
+    unknown_tile_data: UnknownTileData,
+    report: LoadReport,
+}
+
+/// The chunks of a map saved in format version 2 or later, ready to be read one at a time.
+struct PackedChunks {
+    decoder: Decoder<'static, BufReader<File>>,
+    /// the ids of the keys saved before the chunks, which are all of them before format version 4
+    keys: Vec<String>,
+    chunk_count: u64,
+    /// whether each chunk is saved after the ids of the keys it is the first to use
+    chunk_keys: bool,
+}
+
+impl PackedChunks {
+    /// Reads the chunks, each along with the ids of the keys that come before it. Reading stops at the first chunk that cannot be read.
+    fn read(
+        self,
+        report: &mut LoadReport,
+        mut f: impl FnMut(Vec<String>, PackedChunk, &mut LoadReport),
+    ) {
+        let Self {
+            mut decoder,
+            mut keys,
+            chunk_count,
+            chunk_keys,
+        } = self;
+
+        for index in 0..chunk_count {
+            let read: bincode::Result<(Vec<String>, PackedChunk)> = if chunk_keys {
+                bincode::deserialize_from(&mut decoder)
+            } else {
+                bincode::deserialize_from(&mut decoder).map(|chunk| (mem::take(&mut keys), chunk))
+            };
+
+            match read {
+                Ok((keys, chunk)) => f(keys, chunk, report),
+                Err(e) => {
+                    report
+                        .errors
+                        .push(format!("chunk {index} of {chunk_count} is damaged: {e}"));
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// A map as it was saved, with its ids written out rather than looked up.
+#[derive(Debug, Serialize)]
+pub struct MapDump {
+    pub header: MapHeader,
+    pub tiles: Vec<(TileCoord, TileRaw)>,
+    pub terrain: Vec<(TileCoord, String)>,
+    pub report: LoadReport,
+}
+
+/// What could and could not be read from a map.
+#[derive(Debug, Default, Serialize)]
+pub struct LoadReport {
+    /// The number of tiles the map was saved with.
+    pub expected: u64,
+    /// The number of tiles that could be read, including those whose ids are not loaded.
+    pub read: u64,
+    /// What went wrong while reading.
+    pub errors: Vec<String>,
+}
+
+impl LoadReport {
+    /// Whether everything could be read.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// The number of tiles that could not be read.
+    pub fn lost(&self) -> u64 {
+        self.expected.saturating_sub(self.read)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MapHeader {
+    /// The version of the format the map was saved in. Maps from before this was recorded are version 0.
+    #[serde(default)]
+    pub format_version: u32,
+    /// The ids of the tiles and terrain. Only used by maps before format version 2.
     #[serde(default)]
     pub tile_map: Vec<(Id, String)>,
+    /// The ids that the keys in the tiles, terrain and their data stand for. Only used by maps in format version 2, later versions keep them with the tiles.
+    #[serde(default)]
+    pub keys: Vec<String>,
+    /// The number of saved chunks. Only used by maps in format version 2, later versions keep it with the tiles.
+    #[serde(default)]
+    pub chunk_count: u64,
+    /// Which save the header was written by. The tiles record the same, so that a header and tiles from different saves are told apart.
+    #[serde(default)]
+    pub generation: u64,
     #[serde(default)]
     pub data: DataMapRaw,
     #[serde(default)]
     pub tile_count: u64,
+    #[serde(default)]
+    pub transit: Vec<ShipmentRaw>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub generated: Vec<ChunkCoord>,
+    #[serde(default)]
+    pub save_time: Option<SystemTime>,
+    #[serde(default)]
+    pub created: Option<SystemTime>,
+    #[serde(default)]
+    pub play_time: Duration,
+    #[serde(default)]
+    pub tick_count: u64,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub namespaces: Vec<String>,
 }
 
 impl Map {
@@ -79,8 +328,28 @@ impl Map {
             map_name,
 
             tiles: Default::default(),
+            terrain: Default::default(),
+            unknown: Default::default(),
+            unknown_terrain: Default::default(),
             data: Default::default(),
+            unknown_data: Default::default(),
+            unknown_tile_data: Default::default(),
+            transit: Default::default(),
             save_time: None,
+            seed: None,
+            generated: Default::default(),
+            created: Some(SystemTime::now()),
+            play_time: Duration::ZERO,
+            tick_count: 0,
+            description: String::new(),
+        }
+    }
+
+    /// Creates a new map that the world generator fills with the given seed.
+    pub fn new_generated(map_name: String, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..Self::new_empty(map_name)
         }
     }
 
@@ -99,12 +368,250 @@ impl Map {
         Map::path(map_name).join(format!("tiles{MAP_EXT}"))
     }
 
-    pub fn read_header(
-        resource_man: &ResourceManager,
-        map_name: &str,
-    ) -> Option<(MapHeader, Option<SystemTime>)> {
-        let path = Self::header(map_name);
+    /// Gets the path to a map's terrain from its name.
+    pub fn terrain(map_name: &str) -> PathBuf {
+        Map::path(map_name).join(format!("terrain{MAP_EXT}"))
+    }
+
+    /// Gets the names of the files a map is saved in.
+    fn file_names() -> [String; 3] {
+        [
+            format!("header{HEADER_EXT}"),
+            format!("tiles{MAP_EXT}"),
+            format!("terrain{MAP_EXT}"),
+        ]
+    }
+
+    /// Gets the path a file is written to before it replaces the file, so that a crash never leaves the file half written.
+    fn temp_path(path: &Path) -> PathBuf {
+        let mut path = path.as_os_str().to_owned();
+        path.push(TEMP_EXT);
+
+        PathBuf::from(path)
+    }
+
+    /// Gets the path to a map's backups from its name.
+    pub fn backups_path(map_name: &str) -> PathBuf {
+        Map::path(map_name).join(BACKUP_PATH)
+    }
+
+    /// Gets the path to one of a map's backups. Backup 0 is the newest.
+    pub fn backup(map_name: &str, index: usize) -> PathBuf {
+        Map::backups_path(map_name).join(index.to_string())
+    }
+
+    /// Gets the indices of a map's backups, newest first.
+    fn backup_indices(map_name: &str) -> Vec<usize> {
+        let mut indices = fs::read_dir(Self::backups_path(map_name))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .flat_map(|entry| entry.file_name().to_str()?.parse::<usize>().ok())
+            .collect::<Vec<_>>();
+
+        indices.sort();
+
+        indices
+    }
+
+    /// Gets the information about each of a map's backups, newest first. Backups that cannot be read are left out.
+    pub fn backups(map_name: &str) -> Vec<(usize, MapInfo)> {
+        Self::backup_indices(map_name)
+            .into_iter()
+            .flat_map(|index| {
+                let path = Self::backup(map_name, index).join(format!("header{HEADER_EXT}"));
+                let (header, time) = Self::decode_header(&path)?;
+
+                Some((index, MapInfo::from_header(&header.ok()?, time)))
+            })
+            .collect()
+    }
 
+    /// Moves a map's backups one place back, keeping at most the given number, and backs up the map as it is on disk as backup 0.
+    fn rotate_backups(map_name: &str, backup_count: usize) -> io::Result<()> {
+        if backup_count == 0 || !Self::header(map_name).exists() {
+            return Ok(());
+        }
+
+        for index in Self::backup_indices(map_name).into_iter().rev() {
+            let backup = Self::backup(map_name, index);
+
+            if index + 1 >= backup_count {
+                fs::remove_dir_all(backup)?;
+            } else {
+                fs::rename(backup, Self::backup(map_name, index + 1))?;
+            }
+        }
+
+        let backup = Self::backup(map_name, 0);
+        fs::create_dir_all(&backup)?;
+
+        for name in Self::file_names() {
+            let path = Self::path(map_name).join(&name);
+
+            if path.exists() {
+                fs::copy(path, backup.join(name))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks if the map is due to be backed up, which is when its newest backup is older than the given interval, or it has none.
+    fn backup_due(map_name: &str, interval: Duration) -> bool {
+        let age = fs::metadata(Self::backup(map_name, 0).join(format!("header{HEADER_EXT}")))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|time| time.elapsed().ok());
+
+        match age {
+            Some(age) => age >= interval,
+            None => true,
+        }
+    }
+
+    /// Replaces a map with one of its backups. The map as it was becomes the newest backup, so that restoring can be undone.
+    pub fn restore_backup(map_name: &str, index: usize, backup_count: usize) -> io::Result<()> {
+        let backup = Self::backup(map_name, index);
+
+        if !backup.exists() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        // copied before rotating, as rotating moves the backup
+        let mut restored = Vec::new();
+
+        for name in Self::file_names() {
+            let path = Self::path(map_name).join(&name);
+            let from = backup.join(&name);
+
+            if from.exists() {
+                fs::copy(from, Self::temp_path(&path))?;
+                restored.push(path);
+            } else {
+                drop(fs::remove_file(Self::temp_path(&path)));
+            }
+        }
+
+        Self::rotate_backups(map_name, backup_count.max(1))?;
+
+        for name in Self::file_names() {
+            let path = Self::path(map_name).join(name);
+
+            if restored.contains(&path) {
+                fs::rename(Self::temp_path(&path), path)?;
+            } else {
+                drop(fs::remove_file(path));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks if the map holds the files of a map that could not be read, which were moved aside.
+    pub fn is_quarantined(map_name: &str) -> bool {
+        map_name.contains(QUARANTINE_MARK)
+    }
+
+    /// Moves the files of a map that could not be read to a map of their own, so that saving the map does not overwrite them. Gets the name of that map.
+    /// The files of a map that holds files moved aside already stay where they are.
+    fn quarantine(map_name: &str) -> String {
+        if Self::is_quarantined(map_name) {
+            log::warn!("map {map_name} was already moved aside, leaving its files as they are");
+
+            return map_name.to_string();
+        }
+
+        let err_map_name = format!(
+            "{map_name}{QUARANTINE_MARK}{}",
+            Local::now().format("%y%m%d%H%M%S")
+        );
+        let err_path = Self::path(&err_map_name);
+
+        drop(fs::create_dir_all(&err_path));
+
+        for name in Self::file_names() {
+            let path = Self::path(map_name).join(&name);
+
+            if path.exists() {
+                if let Err(e) = fs::rename(&path, err_path.join(name)) {
+                    log::error!("could not move {path:?} out of the way: {e:?}");
+                }
+            }
+        }
+
+        log::warn!("moved the files of map {map_name} to map {err_map_name}");
+
+        err_map_name
+    }
+
+    /// Tells the player that the map could not be read, after moving its files out of the way.
+    fn report_invalid(resource_man: &ResourceManager, map_name: &str) {
+        let err_map_name = Self::quarantine(map_name);
+
+        resource_man.error_man.push(
+            (
+                resource_man.registry.err_ids.invalid_map_data,
+                vec![map_name.to_string(), err_map_name],
+            ),
+            resource_man,
+        );
+    }
+
+    /// Tells the player what could not be read from the map, after moving its files out of the way.
+    fn report_recovered(resource_man: &ResourceManager, map_name: &str, report: &LoadReport) {
+        for error in &report.errors {
+            log::error!("could not fully read map {map_name}: {error}");
+        }
+
+        log::warn!("recovered map {map_name}, losing {} tiles", report.lost());
+
+        let err_map_name = Self::quarantine(map_name);
+
+        resource_man.error_man.push(
+            (
+                resource_man.registry.err_ids.recovered_map_data,
+                vec![
+                    map_name.to_string(),
+                    report.read.to_string(),
+                    report.expected.to_string(),
+                    err_map_name,
+                ],
+            ),
+            resource_man,
+        );
+    }
+
+    /// Reads the elements of a JSON array one at a time into the list, so that the elements before where the array breaks are kept.
+    fn salvage_json_array(reader: impl Read, values: &mut Vec<Value>) -> serde_json::Result<()> {
+        struct Salvage<'a>(&'a mut Vec<Value>);
+
+        impl<'de> Visitor<'de> for Salvage<'_> {
+            type Value = ();
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an array")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                while let Some(value) = seq.next_element::<Value>()? {
+                    self.0.push(value);
+                }
+
+                Ok(())
+            }
+        }
+
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+
+        deserializer.deserialize_seq(Salvage(values))
+    }
+
+    /// Reads the header at the path as it is on disk, in whatever format version it was saved in.
+    fn read_raw_header(path: &Path) -> Option<(serde_json::Result<Value>, Option<SystemTime>)> {
         let file = File::open(path).ok()?;
         let time = file
             .metadata()
@@ -113,172 +620,743 @@ impl Map {
 
         let reader = BufReader::with_capacity(MAP_BUFFER_SIZE, file);
 
-        let decoded: serde_json::Result<MapHeader> = serde_json::from_reader(reader);
+        Some((serde_json::from_reader(reader), time))
+    }
+
+    /// Reads the header at the path, if it is in a format version this game can read.
+    ///
+    /// The header of a map from a newer version of the game is read as it is, so that the map can still be listed.
+    fn decode_header(path: &Path) -> Option<(anyhow::Result<MapHeader>, Option<SystemTime>)> {
+        let (header, time) = Self::read_raw_header(path)?;
+
+        let decoded = header.map_err(anyhow::Error::from).and_then(|header| {
+            match migration::check_version(&header) {
+                Ok(_) | Err(MigrationError::TooNew(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            Ok(serde_json::from_value::<MapHeader>(header)?)
+        });
+
+        Some((decoded, time))
+    }
+
+    /// Reads a map's header.
+    pub fn read_header(
+        resource_man: &ResourceManager,
+        map_name: &str,
+    ) -> Option<(MapHeader, Option<SystemTime>)> {
+        let (decoded, time) = Self::decode_header(&Self::header(map_name))?;
 
         match decoded {
             Ok(v) => Some((v, time)),
             Err(e) => {
-                log::error!("serde: {e:?}");
+                log::error!("could not read the header of map {map_name}: {e:?}");
 
-                let err_map_name =
-                    format!("{}-ERR-{}", map_name, Local::now().format("%y%m%d%H%M%S"));
+                None
+            }
+        }
+    }
 
-                resource_man.error_man.push(
-                    (
-                        resource_man.registry.err_ids.invalid_map_data,
-                        vec![map_name.to_string(), err_map_name],
-                    ),
-                    resource_man,
-                );
+    /// Reads everything in a map that can still be read. The tiles are decoded the way the version the map was saved in encoded them.
+    ///
+    /// None if the header cannot be read, since nothing else can be read without it.
+    fn read(
+        resource_man: &ResourceManager,
+        map_name: &str,
+    ) -> Option<(MapHeader, Contents, Option<SystemTime>)> {
+        let (header, time) = Self::read_raw_header(&Self::header(map_name))?;
+
+        let decoded = header.map_err(anyhow::Error::from).and_then(|header| {
+            if migration::format_version(&header) < PACKED_FORMAT_VERSION {
+                Self::read_json(resource_man, map_name, header)
+            } else {
+                Self::read_packed(resource_man, map_name, header)
+            }
+        });
+
+        match decoded {
+            Ok((header, contents)) => Some((header, contents, time)),
+            Err(e) => {
+                log::error!("could not read map {map_name}: {e:?}");
+
+                // nothing is wrong with the files of a map from a newer version of the game, so they stay where they are
+                if let Some(MigrationError::TooNew(version)) = e.downcast_ref::<MigrationError>() {
+                    resource_man.error_man.push(
+                        (
+                            resource_man.registry.err_ids.too_new_map,
+                            vec![map_name.to_string(), version.to_string()],
+                        ),
+                        resource_man,
+                    );
+
+                    return None;
+                }
+
+                Self::report_invalid(resource_man, map_name);
 
                 None
             }
         }
     }
 
-    pub fn read_tiles(
+    /// Reads everything in a map that can still be read, with its ids written out rather than looked up. Used to inspect maps without loading them.
+    pub fn dump(map_name: &str) -> anyhow::Result<MapDump> {
+        let (header, _) =
+            Self::read_raw_header(&Self::header(map_name)).context("the map has no header")?;
+        let header = header?;
+
+        if migration::format_version(&header) < PACKED_FORMAT_VERSION {
+            Self::dump_json(map_name, header)
+        } else {
+            Self::dump_packed(map_name, header)
+        }
+    }
+
+    /// Reads a map saved before format version 2, which has its tiles in JSON. Tiles that cannot be read are skipped.
+    fn read_json(
         resource_man: &ResourceManager,
         map_name: &str,
-    ) -> Option<Vec<(TileCoord, SerdeTile)>> {
-        let path = Self::tiles(map_name);
+        header: Value,
+    ) -> anyhow::Result<(MapHeader, Contents)> {
+        let dump = Self::dump_json(map_name, header)?;
 
-        let file = File::open(path).ok()?;
-        let decoder = Decoder::new(file).unwrap();
+        let mut contents = Contents {
+            report: dump.report,
+            ..Default::default()
+        };
 
-        let decoded: serde_json::Result<Vec<(TileCoord, SerdeTile)>> =
-            serde_json::from_reader(decoder);
+        for (coord, tile) in dump.tiles {
+            if let Some(id) = resource_man.interner.get(tile.id.as_str()) {
+                let (data, unknown_data) = tile.data.to_data(resource_man);
 
-        match decoded {
-            Ok(v) => Some(v),
+                contents.tiles.insert(coord, (id, tile.tile_modifier));
+                contents.tile_data.insert(coord, data);
+
+                if !unknown_data.is_empty() {
+                    contents.unknown_tile_data.insert(coord, unknown_data);
+                }
+            } else {
+                contents.unknown.insert(coord, tile);
+            }
+        }
+
+        for (coord, name) in dump.terrain {
+            if let Some(id) = resource_man.interner.get(name.as_str()) {
+                contents.terrain.insert(coord, id);
+            } else {
+                contents.unknown_terrain.insert(coord, name);
+            }
+        }
+
+        Ok((dump.header, contents))
+    }
+
+    /// Reads the tiles and terrain of a map saved before format version 2 as they were saved. Tiles that cannot be read are skipped.
+    fn dump_json(map_name: &str, header: Value) -> anyhow::Result<MapDump> {
+        let mut report = LoadReport::default();
+        let mut serde_tiles = Vec::new();
+
+        match File::open(Self::tiles(map_name)) {
+            Ok(file) => {
+                if let Err(e) = Decoder::new(file)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|decoder| Ok(Self::salvage_json_array(decoder, &mut serde_tiles)?))
+                {
+                    report.errors.push(format!("the tiles are cut off: {e}"));
+                }
+            }
+            Err(e) => report.errors.push(format!("the tiles are missing: {e}")),
+        }
+
+        let serde_tiles = Value::Array(serde_tiles);
+
+        migration::check_version(&header)?;
+
+        let header = serde_json::from_value::<MapHeader>(header)?;
+        report.expected = header.tile_count;
+
+        let id_reverse = header.tile_map.iter().cloned().collect::<HashMap<_, _>>();
+
+        let mut tiles = Vec::new();
+
+        for serde_tile in serde_tiles.as_array().into_iter().flatten() {
+            let (coord, SerdeTile(id, tile_modifier, data)) =
+                match <(TileCoord, SerdeTile)>::deserialize(serde_tile) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        report.errors.push(format!("a tile is damaged: {e}"));
+                        continue;
+                    }
+                };
+
+            let Some(name) = id_reverse.get(&id) else {
+                report.errors.push(format!("the tile at {coord} has no id"));
+                continue;
+            };
+
+            report.read += 1;
+
+            tiles.push((
+                coord,
+                TileRaw {
+                    id: name.clone(),
+                    tile_modifier,
+                    data,
+                },
+            ));
+        }
+
+        let terrain = Map::read_terrain(map_name, &mut report)
+            .into_iter()
+            .flat_map(|(coord, id)| Some((coord, id_reverse.get(&id)?.clone())))
+            .collect();
+
+        Ok(MapDump {
+            header,
+            tiles,
+            terrain,
+            report,
+        })
+    }
+
+    /// Opens the chunks of a map saved in format version 2 or later, given the version it was saved in.
+    /// The tiles of format version 2 are only chunks, with the keys and number of chunks in the header instead.
+    fn open_chunks(
+        map_name: &str,
+        saved_version: u32,
+        header: &MapHeader,
+        report: &mut LoadReport,
+    ) -> Option<PackedChunks> {
+        let mut decoder = match File::open(Self::tiles(map_name)).and_then(Decoder::new) {
+            Ok(v) => v,
             Err(e) => {
-                log::error!("serde: {e:?}");
+                report.errors.push(format!("the tiles are missing: {e}"));
+                return None;
+            }
+        };
 
-                let err_map_name =
-                    format!("{}-ERR-{}", map_name, Local::now().format("%y%m%d%H%M%S"));
+        if saved_version < PRELUDE_FORMAT_VERSION {
+            return Some(PackedChunks {
+                decoder,
+                keys: header.keys.clone(),
+                chunk_count: header.chunk_count,
+                chunk_keys: false,
+            });
+        }
 
-                resource_man.error_man.push(
-                    (
-                        resource_man.registry.err_ids.invalid_map_data,
-                        vec![map_name.to_string(), err_map_name],
-                    ),
-                    resource_man,
-                );
+        let chunk_keys = saved_version >= CHUNK_KEYS_FORMAT_VERSION;
 
-                None
+        let prelude = if chunk_keys {
+            bincode::deserialize_from::<_, PackedPrelude>(&mut decoder)
+                .map(|prelude| (prelude.generation, Vec::new(), prelude.chunk_count))
+        } else {
+            bincode::deserialize_from::<_, KeyedPrelude>(&mut decoder)
+                .map(|prelude| (prelude.generation, prelude.keys, prelude.chunk_count))
+        };
+
+        let (generation, keys, chunk_count) = match prelude {
+            Ok(v) => v,
+            Err(e) => {
+                report.errors.push(format!("the tiles are damaged: {e}"));
+                return None;
             }
+        };
+
+        if generation != header.generation {
+            report
+                .errors
+                .push("the header and the tiles are from different saves".to_string());
         }
+
+        Some(PackedChunks {
+            decoder,
+            keys,
+            chunk_count,
+            chunk_keys,
+        })
     }
 
-    /// Loads a map from disk.
-    pub async fn load(
-        game: ActorRef<GameMsg>,
-        resource_man: Arc<ResourceManager>,
+    /// Reads a map saved in format version 2 or later.
+    fn read_packed(
+        resource_man: &ResourceManager,
         map_name: &str,
-    ) -> (Self, TileEntities) {
-        let Some((header, save_time)) = Map::read_header(&resource_man, map_name) else {
-            return (Map::new_empty(map_name.to_string()), Default::default());
+        header: Value,
+    ) -> anyhow::Result<(MapHeader, Contents)> {
+        let saved_version = migration::check_version(&header)?;
+
+        let header = serde_json::from_value::<MapHeader>(header)?;
+
+        let mut contents = Contents::default();
+        let mut report = LoadReport {
+            expected: header.tile_count,
+            ..Default::default()
         };
 
-        let Some(serde_tiles) = Map::read_tiles(&resource_man, map_name) else {
-            return (Map::new_empty(map_name.to_string()), Default::default());
+        if let Some(chunks) = Self::open_chunks(map_name, saved_version, &header, &mut report) {
+            let mut keys = KeyLookup::default();
+
+            chunks.read(&mut report, |new_keys, chunk, report| {
+                keys.extend(&new_keys, &resource_man.interner);
+                let names = keys.names();
+
+                for PackedTile(coord, id, tile_modifier, data) in chunk.tiles {
+                    let Some(name) = names.get(id as usize) else {
+                        report.errors.push(format!("the tile at {coord} has no id"));
+                        continue;
+                    };
+
+                    report.read += 1;
+
+                    if let Some(id) = keys.get(id) {
+                        let (data, unknown_data) = data.to_data(&keys, resource_man);
+
+                        contents.tiles.insert(coord, (id, tile_modifier));
+                        contents.tile_data.insert(coord, data);
+
+                        if !unknown_data.is_empty() {
+                            contents.unknown_tile_data.insert(coord, unknown_data);
+                        }
+                    } else {
+                        contents.unknown.insert(
+                            coord,
+                            TileRaw {
+                                id: name.clone(),
+                                tile_modifier,
+                                data: data.to_raw(names),
+                            },
+                        );
+                    }
+                }
+
+                for (coord, id) in chunk.terrain {
+                    if let Some(id) = keys.get(id) {
+                        contents.terrain.insert(coord, id);
+                    } else if let Some(name) = names.get(id as usize) {
+                        contents.unknown_terrain.insert(coord, name.clone());
+                    }
+                }
+            });
+        }
+
+        contents.report = report;
+
+        Ok((header, contents))
+    }
+
+    /// Reads the tiles and terrain of a map saved in format version 2 or later as they were saved, with their keys written out as ids.
+    fn dump_packed(map_name: &str, header: Value) -> anyhow::Result<MapDump> {
+        let saved_version = migration::check_version(&header)?;
+
+        let header = serde_json::from_value::<MapHeader>(header)?;
+
+        let mut report = LoadReport {
+            expected: header.tile_count,
+            ..Default::default()
         };
+        let mut tiles = Vec::new();
+        let mut terrain = Vec::new();
 
-        let id_reverse = header.tile_map.into_iter().collect::<HashMap<_, _>>();
+        if let Some(chunks) = Self::open_chunks(map_name, saved_version, &header, &mut report) {
+            let mut names = Vec::new();
 
-        let mut tiles = HashMap::new();
-        let mut tile_entities = HashMap::new();
+            chunks.read(&mut report, |new_keys, chunk, report| {
+                names.extend(new_keys);
+                let names = &names;
 
-        for (coord, SerdeTile(id, tile_modifier, data)) in serde_tiles.into_iter() {
-            if let Some(id) = id_reverse
-                .get(&id)
-                .and_then(|id| resource_man.interner.get(id.as_str()))
-            {
-                let tile_entity =
-                    game::new_tile(resource_man.clone(), game.clone(), coord, id, tile_modifier)
-                        .await;
-                let data = data.to_data(&resource_man).into_inner();
-
-                for (key, value) in data {
-                    tile_entity
-                        .send_message(TileEntityMsg::SetDataValue(key, value))
-                        .unwrap();
+                for PackedTile(coord, id, tile_modifier, data) in chunk.tiles {
+                    let Some(name) = names.get(id as usize) else {
+                        report.errors.push(format!("the tile at {coord} has no id"));
+                        continue;
+                    };
+
+                    report.read += 1;
+
+                    tiles.push((
+                        coord,
+                        TileRaw {
+                            id: name.clone(),
+                            tile_modifier,
+                            data: data.to_raw(names),
+                        },
+                    ));
                 }
 
-                tiles.insert(coord, (id, tile_modifier));
-                tile_entities.insert(coord, tile_entity);
-            }
+                terrain.extend(
+                    chunk
+                        .terrain
+                        .into_iter()
+                        .flat_map(|(coord, id)| Some((coord, names.get(id as usize)?.clone()))),
+                );
+            });
+        }
+
+        Ok(MapDump {
+            header,
+            tiles,
+            terrain,
+            report,
+        })
+    }
+
+    /// Reads the terrain of a map saved before format version 2, as far as it can be read. Maps saved before there was terrain have none.
+    pub fn read_terrain(map_name: &str, report: &mut LoadReport) -> Vec<(TileCoord, Id)> {
+        let Ok(file) = File::open(Self::terrain(map_name)) else {
+            return Vec::new();
+        };
+
+        let mut terrain = Vec::new();
+
+        if let Err(e) = Decoder::new(file)
+            .map_err(anyhow::Error::from)
+            .and_then(|decoder| Ok(Self::salvage_json_array(decoder, &mut terrain)?))
+        {
+            report.errors.push(format!("the terrain is cut off: {e}"));
+        }
+
+        terrain
+            .into_iter()
+            .flat_map(|v| {
+                serde_json::from_value(v)
+                    .map_err(|e| {
+                        report
+                            .errors
+                            .push(format!("a terrain tile is damaged: {e}"))
+                    })
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Loads a map from disk, along with the data of its tiles. If parts of the map cannot be read, the rest is loaded and the broken files are moved out of the way.
+    pub fn load(resource_man: &ResourceManager, map_name: &str) -> (Self, TileData) {
+        let Some((header, contents, save_time)) = Map::read(resource_man, map_name) else {
+            return (Map::new_empty(map_name.to_string()), Default::default());
+        };
+
+        if !contents.report.is_complete() {
+            Self::report_recovered(resource_man, map_name, &contents.report);
+        }
+
+        if !contents.unknown.is_empty() {
+            log::warn!(
+                "map {map_name} has {} tiles whose resources are not loaded",
+                contents.unknown.len()
+            );
         }
 
-        let data = header.data.to_data(&resource_man);
+        let (data, unknown_data) = header.data.to_data(resource_man);
+        let transit = Transit::from_raw(&header.transit, resource_man);
 
         (
             Self {
                 map_name: map_name.to_string(),
 
-                tiles,
+                tiles: contents.tiles,
+                terrain: contents.terrain,
+                unknown: contents.unknown,
+                unknown_terrain: contents.unknown_terrain,
                 data,
+                unknown_data,
+                unknown_tile_data: contents.unknown_tile_data,
+                transit,
 
-                save_time,
+                save_time: header.save_time.or(save_time),
+                seed: header.seed,
+                generated: header.generated.into_iter().collect(),
+                created: header.created,
+                play_time: header.play_time,
+                tick_count: header.tick_count,
+                description: header.description,
             },
-            tile_entities,
+            contents.tile_data,
         )
     }
 
-    /// Saves a map to disk.
-    pub async fn save(&self, interner: &Interner, tile_entities: &TileEntities) {
-        drop(fs::create_dir_all(Map::path(&self.map_name)));
+    /// Gets the namespaces of the tiles and terrain on the map, including those that are not loaded.
+    pub fn namespaces(&self, interner: &Interner) -> Vec<String> {
+        let mut namespaces = self
+            .tiles
+            .values()
+            .map(|(id, _)| *id)
+            .chain(self.terrain.values().cloned())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .flat_map(|id| interner.resolve(id))
+            .chain(self.unknown.values().map(|tile| tile.id.as_str()))
+            .chain(self.unknown_terrain.values().map(String::as_str))
+            .flat_map(|id| {
+                id.split_once(':')
+                    .map(|(namespace, _)| namespace.to_string())
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
 
-        let header = Self::header(&self.map_name);
-        let header = File::create(header).unwrap();
+        namespaces.sort();
 
-        let mut header_writer = BufWriter::with_capacity(MAP_BUFFER_SIZE, header);
+        namespaces
+    }
+
+    /// Gets the information about the map, as it is now.
+    pub fn info(&self, interner: &Interner) -> MapInfo {
+        MapInfo {
+            tile_count: (self.tiles.len() + self.unknown.len()) as u64,
+            save_time: self.save_time,
+            created: self.created,
+            play_time: self.play_time,
+            tick_count: self.tick_count,
+            version: Some(VERSION.to_string()),
+            description: self.description.clone(),
+            namespaces: self.namespaces(interner),
+            format_version: MAP_FORMAT_VERSION,
+        }
+    }
+
+    /// Saves a map to disk, along with the data of its tiles. The tiles are packed and written one chunk at a time, compressed with the given zstd level.
+    ///
+    /// The files are written next to the old ones and only then replace them. The old ones are backed up first if the newest backup is older than the given interval, keeping the given number of backups.
+    pub fn save(
+        &self,
+        interner: &Interner,
+        tile_data: &TileData,
+        compression_level: i32,
+        backup_count: usize,
+        backup_interval: Duration,
+    ) -> io::Result<()> {
+        let saved_version = Self::read_raw_header(&Self::header(&self.map_name))
+            .and_then(|(header, _)| header.ok())
+            .map(|header| migration::format_version(&header));
 
-        let tiles = Self::tiles(&self.map_name);
-        let tiles = File::create(tiles).unwrap();
+        // the map on disk could not be read, so what the game has is not all of it
+        if saved_version.is_some_and(|version| version > MAP_FORMAT_VERSION) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the map was saved by a newer version of the game",
+            ));
+        }
+
+        fs::create_dir_all(Map::path(&self.map_name))?;
+
+        let mut chunks = HashMap::<ChunkCoord, HashSet<TileCoord>>::new();
+
+        for coord in self
+            .tiles
+            .keys()
+            .chain(self.terrain.keys())
+            .chain(self.unknown.keys())
+            .chain(self.unknown_terrain.keys())
+        {
+            chunks.entry((*coord).into()).or_default().insert(*coord);
+        }
+
+        let generation = random::<u64>();
+
+        let tiles_path = Self::tiles(&self.map_name);
+        let tiles = File::create(Self::temp_path(&tiles_path))?;
 
         let tiles_writer = BufWriter::with_capacity(MAP_BUFFER_SIZE, tiles);
-        let mut tiles_encoder = Encoder::new(tiles_writer, 0).unwrap();
+        let mut tiles_encoder = Encoder::new(tiles_writer, compression_level)?;
 
-        let mut tile_map = HashMap::new();
-        let mut serde_tiles = Vec::new();
+        bincode::serialize_into(
+            &mut tiles_encoder,
+            &PackedPrelude {
+                generation,
+                chunk_count: chunks.len() as u64,
+            },
+        )
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
 
-        for (coord, (id, tile_modifier)) in self.tiles.iter() {
-            if let Some(tile_entity) = tile_entities.get(coord) {
-                if !tile_map.contains_key(id) {
-                    tile_map.insert(*id, interner.resolve(*id).unwrap().to_string());
+        let mut keys = KeyTable::default();
+        let mut tile_count = 0;
+
+        for coords in chunks.values() {
+            let mut chunk = PackedChunk::default();
+
+            for coord in coords {
+                if let Some(((id, tile_modifier), data)) =
+                    self.tiles.get(coord).zip(tile_data.get(coord))
+                {
+                    if let Some(id) = keys.key(interner, *id) {
+                        let mut data = data.to_packed(interner, &mut keys);
+
+                        if let Some(unknown_data) = self.unknown_tile_data.get(coord) {
+                            data.merge(unknown_data.to_packed(&mut keys));
+                        }
+
+                        chunk
+                            .tiles
+                            .push(PackedTile(*coord, id, *tile_modifier, data));
+                    }
                 }
 
-                let data = tile_entity
-                    .call(TileEntityMsg::GetData, None)
-                    .await
-                    .unwrap()
-                    .unwrap();
-                let data = data.to_raw(interner);
+                if let Some(tile) = self.unknown.get(coord) {
+                    if !self.tiles.contains_key(coord) {
+                        let id = keys.key_raw(&tile.id);
+                        let data = tile.data.to_packed(&mut keys);
+
+                        chunk
+                            .tiles
+                            .push(PackedTile(*coord, id, tile.tile_modifier, data));
+                    }
+                }
 
-                serde_tiles.push((coord, SerdeTile(*id, *tile_modifier, data)));
+                if let Some(id) = self
+                    .terrain
+                    .get(coord)
+                    .and_then(|id| keys.key(interner, *id))
+                    .or_else(|| self.unknown_terrain.get(coord).map(|id| keys.key_raw(id)))
+                {
+                    chunk.terrain.push((*coord, id));
+                }
             }
+
+            tile_count += chunk.tiles.len() as u64;
+
+            // the chunk goes after the ids of the keys it is the first to use, so that it can be read without the chunks after it
+            bincode::serialize_into(&mut tiles_encoder, &(keys.take_new(), chunk))
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
         }
 
-        let mut tile_map = tile_map.into_iter().collect::<Vec<_>>();
-        tile_map.sort_by_key(|v| v.0);
+        let mut tiles_writer = tiles_encoder.finish()?;
+        tiles_writer.flush()?;
+        tiles_writer.get_ref().sync_all()?;
+
+        let mut data = self.data.to_raw(interner);
+        data.merge(&self.unknown_data);
 
-        let data = self.data.to_raw(interner);
-        let tile_count = serde_tiles.len() as u64;
+        let header_path = Self::header(&self.map_name);
+        let header = File::create(Self::temp_path(&header_path))?;
+
+        let mut header_writer = BufWriter::with_capacity(MAP_BUFFER_SIZE, header);
 
         serde_json::to_writer(
             &mut header_writer,
             &MapHeader {
-                tile_map,
+                format_version: MAP_FORMAT_VERSION,
+                tile_map: Vec::new(),
+                keys: Vec::new(),
+                chunk_count: 0,
+                generation,
                 data,
                 tile_count,
+                transit: self.transit.to_raw(interner),
+                seed: self.seed,
+                generated: self.generated.iter().cloned().collect(),
+                save_time: self.save_time,
+                created: self.created,
+                play_time: self.play_time,
+                tick_count: self.tick_count,
+                version: Some(VERSION.to_string()),
+                description: self.description.clone(),
+                namespaces: self.namespaces(interner),
             },
-        )
-        .unwrap();
+        )?;
+
+        header_writer.flush()?;
+        header_writer.get_ref().sync_all()?;
+
+        if Self::backup_due(&self.map_name, backup_interval) {
+            if let Err(e) = Self::rotate_backups(&self.map_name, backup_count) {
+                log::warn!("could not back up map {}: {e:?}", self.map_name);
+            }
+        }
+
+        // the terrain is saved along with the tiles since format version 2
+        drop(fs::remove_file(Self::terrain(&self.map_name)));
+
+        // the tiles say which save they are from, so a crash between these leaves a map whose parts can still be read, and are known to not match
+        fs::rename(Self::temp_path(&tiles_path), tiles_path)?;
+        fs::rename(Self::temp_path(&header_path), header_path)?;
+
+        Ok(())
+    }
+
+    /// Packs everything in a map's folder, including its backups, into one file at the path.
+    pub fn export(map_name: &str, path: &Path) -> io::Result<()> {
+        let file = File::create(path)?;
+        let encoder = Encoder::new(BufWriter::with_capacity(MAP_BUFFER_SIZE, file), 0)?;
+
+        let mut archive = tar::Builder::new(encoder);
+        archive.follow_symlinks(false);
+        archive.append_dir_all(".", Self::path(map_name))?;
+
+        let mut writer = archive.into_inner()?.finish()?;
+        writer.flush()?;
+
+        log::info!("Exported map {map_name} to {path:?}");
+
+        Ok(())
+    }
+
+    /// Unpacks a map exported with [`Map::export`] as a new map named after the file, never replacing a map that is already there. Gets the name of the new map.
+    pub fn import(path: &Path) -> io::Result<String> {
+        let file_name = path
+            .file_name()
+            .and_then(|v| v.to_str())
+            .unwrap_or_default();
+        let name = Self::sanitize_name(
+            file_name
+                .strip_suffix(ARCHIVE_EXT)
+                .unwrap_or(file_name)
+                .to_string(),
+        );
+
+        let mut map_name = name.clone();
+        let mut n = 1;
+
+        while Self::path(&map_name).exists() {
+            n += 1;
+            map_name = format!("{name}_{n}");
+        }
+
+        let result = Self::unpack(path, &Self::path(&map_name));
 
-        serde_json::to_writer(&mut tiles_encoder, &serde_tiles).unwrap();
+        if result.is_err() || !Self::header(&map_name).exists() {
+            drop(fs::remove_dir_all(Self::path(&map_name)));
+
+            return result.and(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the file does not contain a map",
+            )));
+        }
+
+        log::info!("Imported map {map_name} from {path:?}");
+
+        Ok(map_name)
+    }
+
+    /// Unpacks the files and folders in the archive at the path into the folder. Anything else, such as links, is skipped, and an archive with paths that lead out of the folder is refused.
+    fn unpack(path: &Path, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        let decoder = Decoder::new(File::open(path)?)?;
+        let mut archive = tar::Archive::new(decoder);
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+
+            // unpacking would skip these or put them in the folder, but an archive with them was not exported by the game
+            if entry
+                .path()?
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "the file has a path outside of the map: {:?}",
+                        entry.path()?
+                    ),
+                ));
+            }
+
+            if entry.header().entry_type().is_file() || entry.header().entry_type().is_dir() {
+                entry.unpack_in(dir)?;
+            }
+        }
 
-        header_writer.flush().unwrap();
-        tiles_encoder.do_finish().unwrap();
+        Ok(())
     }
 
     /// Sanitizes the name to ensure that the map can be used without problems on all platforms. This includes removing leading/trailing whitespace and periods, replacing non-alphanumeric characters, and replacing Windows disallowed names.
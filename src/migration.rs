@@ -0,0 +1,60 @@
+//! The versions of the map format.
+//!
+//! Every older format can still be read as it is, so maps are not upgraded when they are loaded. The header keeps the same fields in every version, and the loader picks how to read the tiles from the version the map was saved in:
+//!
+//! - version 0 is the same as version 1, apart from not recording its version
+//! - version 1 saves the tiles as JSON
+//! - version 2 saves the tiles packed in binary chunks, with the keys and number of chunks in the header
+//! - version 3 moves the keys and the number of chunks from the header to the start of the tiles
+//! - version 4 saves each key along with the first chunk that uses it, instead of all of them at the start of the tiles
+//!
+//! A change that the loader cannot handle this way needs its header or tiles to be converted here before they are read.
+
+use serde_json::Value;
+use thiserror::Error;
+
+/// The version of the map format that maps are saved in.
+pub const MAP_FORMAT_VERSION: u32 = 4;
+
+/// The first format version that saves tiles packed in binary chunks, instead of as JSON.
+pub const PACKED_FORMAT_VERSION: u32 = 2;
+
+/// The first format version that starts the tiles with the keys and number of chunks they were saved with, instead of keeping them in the header.
+pub const PRELUDE_FORMAT_VERSION: u32 = 3;
+
+/// The first format version that saves each key with the first chunk that uses it, so that every chunk can be written as soon as it is packed.
+pub const CHUNK_KEYS_FORMAT_VERSION: u32 = 4;
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("the map is in format version {0}, which is newer than this game supports ({MAP_FORMAT_VERSION})")]
+    TooNew(u32),
+    #[error("the map in format version {0} could not be read: {1}")]
+    Failed(u32, &'static str),
+}
+
+/// Gets the format version a map's header was saved in. Maps from before this was recorded are version 0.
+pub fn format_version(header: &Value) -> u32 {
+    header
+        .get("format_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32
+}
+
+/// Checks that a map's header is in a format version this game can read, and gets that version.
+pub fn check_version(header: &Value) -> Result<u32, MigrationError> {
+    let version = format_version(header);
+
+    if version > MAP_FORMAT_VERSION {
+        return Err(MigrationError::TooNew(version));
+    }
+
+    if !header.is_object() {
+        return Err(MigrationError::Failed(
+            version,
+            "the header is not an object",
+        ));
+    }
+
+    Ok(version)
+}
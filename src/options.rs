@@ -10,11 +10,14 @@ use automancy_defs::log;
 use automancy_defs::math::{Double, Float};
 
 use crate::input::{KeyAction, DEFAULT_KEYMAP};
+use crate::simulation::TickEngine;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Options {
     pub graphics: GraphicsOptions,
     pub audio: AudioOptions,
+    #[serde(default)]
+    pub game: GameOptions,
     pub keymap: HashMap<VirtualKeyCode, KeyAction>,
 }
 
@@ -23,6 +26,7 @@ impl Default for Options {
         Self {
             graphics: Default::default(),
             audio: Default::default(),
+            game: Default::default(),
             keymap: DEFAULT_KEYMAP.iter().cloned().collect(),
         }
     }
@@ -108,3 +112,27 @@ impl Default for AudioOptions {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameOptions {
+    /// Which backend runs the tile entities. Takes effect on restart.
+    pub tick_engine: TickEngine,
+    /// The zstd level maps are saved with. Higher is smaller but slower. Takes effect on restart.
+    pub save_compression: i32,
+    /// How many backups to keep of each map. A map is backed up when it is saved, at most once per autosave interval. Takes effect on restart.
+    pub backup_count: usize,
+    /// How many seconds apart the map is saved on its own. Zero turns autosaving off. Takes effect on restart.
+    pub autosave_interval: u64,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        Self {
+            tick_engine: Default::default(),
+            save_compression: 3,
+            backup_count: 3,
+            autosave_interval: 300,
+        }
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::Arc;
+
+use ractor::rpc::CallResult;
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use automancy_defs::coord::TileCoord;
+use automancy_defs::hashbrown::{HashMap, HashSet};
+use automancy_defs::id::Id;
+use automancy_defs::log;
+use automancy_resources::data::DataMap;
+use automancy_resources::ResourceManager;
+
+use crate::game::{new_tile, GameMsg, TickUnit};
+use crate::map::TileData;
+use crate::tile_entity::{TileEntity, TileEntityMsg, TileEntityState, TileModifier};
+use crate::util::actor::multi_call_iter;
+
+/// How many tiles one worker ticks in a row, in the batched engine.
+pub const TICK_BATCH_SIZE: usize = 256;
+/// How many messages between tiles get delivered at most in one go. The rest is carried over to the next tick.
+pub const MAX_RESOLVE_STEPS: usize = 65536;
+
+/// The backend that runs the tile entities.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TickEngine {
+    /// Every tile entity is its own actor.
+    #[default]
+    Actors,
+    /// The game stores every tile entity, and ticks them in parallel batches.
+    Batched,
+}
+
+/// Makes the reply to a message that has no tile entity to go to, so that its sender does not wait on it.
+fn undeliverable(message: TileEntityMsg) -> Option<GameMsg> {
+    match message {
+        TileEntityMsg::Transaction { root_coord, .. } => Some(GameMsg::ForwardMsgToTile(
+            root_coord,
+            TileEntityMsg::TransactionResult { result: Err(()) },
+        )),
+        _ => None,
+    }
+}
+
+/// A tile entity run by the game itself.
+#[derive(Debug)]
+struct Slot {
+    entity: TileEntity,
+    state: TileEntityState,
+}
+
+/// Stores the tile entities contiguously, ticks them in parallel batches, then delivers the messages between them in a deterministic order.
+#[derive(Debug, Default)]
+pub struct Simulation {
+    /// the tile entities
+    slots: Vec<Slot>,
+    /// where each tile entity is in the slots
+    indices: HashMap<TileCoord, usize>,
+    /// the messages between tiles that could not be delivered in the last tick
+    pending: VecDeque<GameMsg>,
+    /// the actors standing in for the tile entities that were handed out
+    proxies: HashMap<TileCoord, ActorRef<TileEntityMsg>>,
+    /// the tile entity that takes the messages for each other position a multi-hex tile takes up
+    aliases: HashMap<TileCoord, TileCoord>,
+}
+
+impl Simulation {
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn contains(&self, coord: TileCoord) -> bool {
+        self.indices.contains_key(&coord)
+    }
+
+    /// Adds a tile entity with the given data, replacing the one at its position.
+    pub fn insert(&mut self, entity: TileEntity, data: DataMap) {
+        self.remove(entity.coord);
+
+        let mut state = TileEntityState::new(None);
+        entity.handle_message(&mut state, TileEntityMsg::SetData(data));
+        state.take_outbox();
+
+        self.indices.insert(entity.coord, self.slots.len());
+        self.slots.push(Slot { entity, state });
+    }
+
+    /// Removes the tile entity at the given position. Returns whether there was one.
+    pub fn remove(&mut self, coord: TileCoord) -> bool {
+        self.stop_proxy(coord);
+
+        let Some(index) = self.indices.remove(&coord) else {
+            return false;
+        };
+
+        self.slots.swap_remove(index);
+
+        if let Some(moved) = self.slots.get(index) {
+            self.indices.insert(moved.entity.coord, index);
+        }
+
+        true
+    }
+
+    /// Moves the tile entities, replacing whatever is at the destinations.
+    pub fn move_all(&mut self, moves: &[(TileCoord, TileCoord)]) {
+        for (_, new_coord) in moves {
+            if !moves.iter().any(|(coord, _)| coord == new_coord) {
+                self.remove(*new_coord);
+            }
+        }
+
+        let moved = moves
+            .iter()
+            .flat_map(|(coord, new_coord)| {
+                self.stop_proxy(*coord);
+
+                self.indices.remove(coord).map(|index| (index, *new_coord))
+            })
+            .collect::<Vec<_>>();
+
+        for (index, new_coord) in moved {
+            self.slots[index].entity.coord = new_coord;
+            self.indices.insert(new_coord, index);
+        }
+    }
+
+    /// Stops and removes every tile entity.
+    pub fn clear(&mut self) {
+        for (_, proxy) in self.proxies.drain() {
+            proxy.stop(Some("Loading new map".to_string()));
+        }
+
+        self.slots.clear();
+        self.indices.clear();
+        self.pending.clear();
+        self.aliases.clear();
+    }
+
+    /// Makes the messages for the given position go to the tile entity at the owner's position.
+    pub fn alias(&mut self, coord: TileCoord, owner: TileCoord) {
+        self.aliases.insert(coord, owner);
+    }
+
+    pub fn unalias(&mut self, coord: TileCoord) {
+        self.aliases.remove(&coord);
+    }
+
+    fn stop_proxy(&mut self, coord: TileCoord) {
+        if let Some(proxy) = self.proxies.remove(&coord) {
+            proxy.stop(Some("Removed from game".to_string()));
+        }
+    }
+
+    /// Gets an actor that forwards its messages to the tile entity at the given position.
+    pub async fn proxy(
+        &mut self,
+        game: ActorRef<GameMsg>,
+        coord: TileCoord,
+    ) -> Option<ActorRef<TileEntityMsg>> {
+        if !self.contains(coord) {
+            return None;
+        }
+
+        if let Some(proxy) = self.proxies.get(&coord) {
+            return Some(proxy.clone());
+        }
+
+        let (proxy, _handle) =
+            Actor::spawn_linked(None, TileProxy { coord }, game.clone(), game.get_cell())
+                .await
+                .unwrap();
+
+        self.proxies.insert(coord, proxy.clone());
+
+        Some(proxy)
+    }
+
+    /// Collects the data of every tile entity.
+    pub fn all_data(&self) -> TileData {
+        self.slots
+            .iter()
+            .map(|Slot { entity, state }| (entity.coord, state.data().clone()))
+            .collect()
+    }
+
+    /// Collects the data of the tile entities at the given positions.
+    pub fn data(&mut self, coords: impl IntoIterator<Item = TileCoord>) -> TileData {
+        coords
+            .into_iter()
+            .flat_map(|coord| {
+                let index = *self.indices.get(&coord)?;
+
+                Some((coord, self.slots[index].state.collect_data()))
+            })
+            .collect()
+    }
+
+    /// Handles a message for the tile entity at the given position right away. Returns the messages meant for the game.
+    pub fn send(&mut self, coord: TileCoord, message: TileEntityMsg) -> Vec<GameMsg> {
+        self.resolve(VecDeque::from([GameMsg::ForwardMsgToTile(coord, message)]))
+    }
+
+    /// Ticks every tile entity that is not sleeping, in parallel batches. Returns the messages meant for the game.
+    pub fn tick(&mut self, tick_count: TickUnit, sleeping: &HashSet<TileCoord>) -> Vec<GameMsg> {
+        self.slots
+            .par_chunks_mut(TICK_BATCH_SIZE)
+            .for_each(|batch| {
+                for Slot { entity, state } in batch {
+                    if !sleeping.contains(&entity.coord) {
+                        entity.handle_message(state, TileEntityMsg::Tick { tick_count });
+                    }
+                }
+            });
+
+        let mut outboxes = self
+            .slots
+            .iter_mut()
+            .map(|Slot { entity, state }| (entity.coord, state.take_outbox()))
+            .filter(|(_, outbox)| !outbox.is_empty())
+            .collect::<Vec<_>>();
+
+        // the slots get reordered on removal, so the position decides who goes first
+        outboxes.sort_unstable_by_key(|(coord, _)| (coord.q(), coord.r()));
+
+        let mut queue = mem::take(&mut self.pending);
+        queue.extend(outboxes.into_iter().flat_map(|(_, outbox)| outbox));
+
+        self.resolve(queue)
+    }
+
+    /// Delivers the queued messages to their tile entities in order, along with the messages that result from them.
+    /// Returns the messages meant for the game.
+    fn resolve(&mut self, mut queue: VecDeque<GameMsg>) -> Vec<GameMsg> {
+        let mut to_game = vec![];
+        let mut steps = 0;
+
+        while let Some(message) = queue.pop_front() {
+            if steps == MAX_RESOLVE_STEPS {
+                log::warn!(
+                    "simulation: too many messages between tiles, delaying {} to the next tick",
+                    queue.len() + 1
+                );
+
+                queue.push_front(message);
+                break;
+            }
+            steps += 1;
+
+            match message {
+                GameMsg::ForwardMsgToTile(coord, message) => {
+                    let coord = self.aliases.get(&coord).cloned().unwrap_or(coord);
+
+                    if let Some(index) = self.indices.get(&coord).cloned() {
+                        let Slot { entity, state } = &mut self.slots[index];
+
+                        entity.handle_message(state, message);
+                        queue.extend(state.take_outbox());
+                    } else {
+                        queue.extend(undeliverable(message));
+                    }
+                }
+                message => to_game.push(message),
+            }
+        }
+
+        self.pending.extend(queue);
+
+        to_game
+    }
+}
+
+/// A stand-in actor for a tile entity run by the game, forwarding all of its messages to the game.
+pub struct TileProxy {
+    pub coord: TileCoord,
+}
+
+#[async_trait::async_trait]
+impl Actor for TileProxy {
+    type Msg = TileEntityMsg;
+    type State = ActorRef<GameMsg>;
+    type Arguments = ActorRef<GameMsg>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        game: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(game)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        game: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        game.send_message(GameMsg::ForwardMsgToTile(self.coord, message))?;
+
+        Ok(())
+    }
+}
+
+/// The tile entities of the game, run by one of the tick engines.
+#[derive(Debug)]
+pub enum TileEntities {
+    Actors(HashMap<TileCoord, ActorRef<TileEntityMsg>>),
+    Batched(Simulation),
+}
+
+impl TileEntities {
+    pub fn new(engine: TickEngine) -> Self {
+        match engine {
+            TickEngine::Actors => Self::Actors(Default::default()),
+            TickEngine::Batched => Self::Batched(Default::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Actors(actors) => actors.len(),
+            Self::Batched(simulation) => simulation.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, coord: TileCoord) -> bool {
+        match self {
+            Self::Actors(actors) => actors.contains_key(&coord),
+            Self::Batched(simulation) => simulation.contains(coord),
+        }
+    }
+
+    /// Creates a tile entity at the given position with the given data, replacing the old one.
+    pub async fn insert(
+        &mut self,
+        resource_man: Arc<ResourceManager>,
+        game: ActorRef<GameMsg>,
+        coord: TileCoord,
+        id: Id,
+        tile_modifier: TileModifier,
+        data: DataMap,
+    ) {
+        self.remove(coord);
+
+        match self {
+            Self::Actors(actors) => {
+                let tile_entity = new_tile(resource_man, game, coord, id, tile_modifier).await;
+
+                if !data.is_empty() {
+                    tile_entity
+                        .send_message(TileEntityMsg::SetData(data))
+                        .unwrap();
+                }
+
+                actors.insert(coord, tile_entity);
+            }
+            Self::Batched(simulation) => simulation.insert(
+                TileEntity {
+                    id,
+                    coord,
+                    tile_modifier,
+                    resource_man,
+                },
+                data,
+            ),
+        }
+    }
+
+    /// Stops and removes the tile entity at the given position.
+    pub fn remove(&mut self, coord: TileCoord) {
+        match self {
+            Self::Actors(actors) => {
+                if let Some(tile_entity) = actors.remove(&coord) {
+                    tile_entity.stop(Some("Removed from game".to_string()));
+                }
+            }
+            Self::Batched(simulation) => {
+                simulation.remove(coord);
+            }
+        }
+    }
+
+    /// Stops and removes every tile entity.
+    pub fn clear(&mut self) {
+        match self {
+            Self::Actors(actors) => {
+                for (_, tile_entity) in actors.drain() {
+                    tile_entity.stop(Some("Loading new map".to_string()));
+                }
+            }
+            Self::Batched(simulation) => simulation.clear(),
+        }
+    }
+
+    /// Moves the tile entities, replacing whatever is at the destinations.
+    pub fn move_all(&mut self, moves: &[(TileCoord, TileCoord)]) {
+        match self {
+            Self::Actors(actors) => {
+                let moved = moves
+                    .iter()
+                    .flat_map(|(coord, new_coord)| {
+                        actors
+                            .remove(coord)
+                            .map(|tile_entity| (*new_coord, tile_entity))
+                    })
+                    .collect::<Vec<_>>();
+
+                for (new_coord, tile_entity) in moved {
+                    if let Some(old) = actors.insert(new_coord, tile_entity) {
+                        old.stop(Some("Removed from game".to_string()));
+                    }
+                }
+            }
+            Self::Batched(simulation) => simulation.move_all(moves),
+        }
+    }
+
+    /// Makes the messages for the given position go to the tile entity at the owner's position, if the game runs the tile entities itself.
+    /// Actors send their messages through the game, which does this for them.
+    pub fn alias(&mut self, coord: TileCoord, owner: TileCoord) {
+        if let Self::Batched(simulation) = self {
+            simulation.alias(coord, owner);
+        }
+    }
+
+    pub fn unalias(&mut self, coord: TileCoord) {
+        if let Self::Batched(simulation) = self {
+            simulation.unalias(coord);
+        }
+    }
+
+    /// Sends a message to the tile entity at the given position. Returns the messages meant for the game, if the game runs the tile entities itself.
+    pub fn send(&mut self, coord: TileCoord, message: TileEntityMsg) -> Vec<GameMsg> {
+        match self {
+            Self::Actors(actors) => {
+                if let Some(tile_entity) = actors.get(&coord) {
+                    tile_entity.send_message(message).unwrap();
+
+                    vec![]
+                } else {
+                    undeliverable(message).into_iter().collect()
+                }
+            }
+            Self::Batched(simulation) => simulation.send(coord, message),
+        }
+    }
+
+    /// Gets an actor that handles the messages of the tile entity at the given position.
+    pub async fn actor(
+        &mut self,
+        game: ActorRef<GameMsg>,
+        coord: TileCoord,
+    ) -> Option<ActorRef<TileEntityMsg>> {
+        match self {
+            Self::Actors(actors) => actors.get(&coord).cloned(),
+            Self::Batched(simulation) => simulation.proxy(game, coord).await,
+        }
+    }
+
+    /// Collects the data of every tile entity.
+    pub async fn all_data(&self) -> TileData {
+        match self {
+            Self::Actors(actors) => multi_call_iter(
+                actors.values(),
+                actors.len(),
+                TileEntityMsg::GetDataWithCoord,
+                None,
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(CallResult::unwrap)
+            .collect(),
+            Self::Batched(simulation) => simulation.all_data(),
+        }
+    }
+
+    /// Collects the data of the tile entities at the given positions.
+    pub async fn data(&mut self, coords: impl IntoIterator<Item = TileCoord>) -> TileData {
+        match self {
+            Self::Actors(actors) => {
+                let actors = coords
+                    .into_iter()
+                    .flat_map(|coord| actors.get(&coord))
+                    .collect::<Vec<_>>();
+
+                multi_call_iter(
+                    actors.iter().copied(),
+                    actors.len(),
+                    TileEntityMsg::GetDataWithCoord,
+                    None,
+                )
+                .await
+                .unwrap()
+                .into_iter()
+                .map(CallResult::unwrap)
+                .collect()
+            }
+            Self::Batched(simulation) => simulation.data(coords),
+        }
+    }
+
+    /// Ticks every tile entity that is not sleeping. Returns the messages meant for the game, if the game runs the tile entities itself.
+    pub fn tick(&mut self, tick_count: TickUnit, sleeping: &HashSet<TileCoord>) -> Vec<GameMsg> {
+        match self {
+            Self::Actors(actors) => {
+                actors
+                    .par_iter()
+                    .filter(|(coord, _)| !sleeping.contains(*coord))
+                    .for_each(|(_, tile_entity)| {
+                        if let Err(e) = tile_entity.send_message(TileEntityMsg::Tick { tick_count })
+                        {
+                            log::error!("{e:?}");
+                        }
+                    });
+
+                vec![]
+            }
+            Self::Batched(simulation) => simulation.tick(tick_count, sleeping),
+        }
+    }
+}
@@ -1,9 +1,8 @@
 use std::mem;
 use std::sync::Arc;
 
-use ractor::{Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
-use rand::{thread_rng, RngCore};
-use rhai::{CallFnOptions, Dynamic, ImmutableString, Scope, INT};
+use ractor::{Actor, ActorProcessingErr, ActorRef, MessagingErr, RpcReplyPort};
+use rhai::{CallFnOptions, Dynamic, ImmutableString, Scope, AST, INT};
 
 use automancy_defs::coord::TileCoord;
 use automancy_defs::id::Id;
@@ -13,6 +12,7 @@ use automancy_resources::ResourceManager;
 
 use crate::game::{GameMsg, TickUnit};
 use crate::tile_entity::TileEntityMsg::*;
+use crate::transit::Shipment;
 
 fn rhai_call_options(rhai_state: &mut Dynamic) -> CallFnOptions {
     CallFnOptions::new()
@@ -21,6 +21,14 @@ fn rhai_call_options(rhai_state: &mut Dynamic) -> CallFnOptions {
         .bind_this_ptr(rhai_state)
 }
 
+/// The terrain under a tile, as the scripts see it.
+fn rhai_terrain(terrain: Option<Id>) -> Dynamic {
+    match terrain {
+        Some(id) => Dynamic::from_int(id.into()),
+        None => Dynamic::UNIT,
+    }
+}
+
 pub type TileModifier = INT;
 
 #[derive(Debug, Clone)]
@@ -35,12 +43,16 @@ pub struct TileEntity {
 }
 
 pub const RHAI_DATA_MAP_KEY: &str = "data";
+/// How many ticks a tile waits on the results of its transactions, before it asks the game how many still wait on a result.
+pub const TRANSACTION_TIMEOUT: TickUnit = 60;
 
 /// Represents a tile entity's state. A tile entity is the actor that allows the tile to take, process, and output resources.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct TileEntityState {
-    /// A handle to the game.
-    game: ActorRef<GameMsg>,
+    /// A handle to the game. None if the game runs the tile itself, in which case messages are queued in the outbox.
+    game: Option<ActorRef<GameMsg>>,
+    /// The messages to the game that have not been collected yet.
+    outbox: Vec<GameMsg>,
 
     /// The rhai object map
     rhai_map: rhai::Map, // TODO deprecate the data map entirely
@@ -53,12 +65,30 @@ pub struct TileEntityState {
 
     /// Are adjacent tiles requirement fulfilled
     adjacent_fulfilled: bool,
+
+    /// The terrain under the tile, if any
+    terrain: Option<Id>,
+
+    /// Has the tile told the game it is idle
+    sleeping: bool,
+
+    /// Has the tile told the game its data changed, since the game last collected it
+    data_changed: bool,
+
+    /// How many transactions the tile made that have not got a result yet
+    in_flight: usize,
+    /// How many ticks the tile has been waiting on those results
+    waited: TickUnit,
+
+    /// The state of the numbers the tile's scripts get as `random`, seeded every tick from the tick and the coord
+    random: u64,
 }
 
 impl TileEntityState {
-    fn new(game: ActorRef<GameMsg>) -> Self {
+    pub fn new(game: Option<ActorRef<GameMsg>>) -> Self {
         Self {
             game,
+            outbox: vec![],
 
             rhai_map: rhai::Map::from([(
                 RHAI_DATA_MAP_KEY.into(),
@@ -69,6 +99,46 @@ impl TileEntityState {
             data: DataMap::default(),
 
             adjacent_fulfilled: true,
+
+            terrain: None,
+
+            sleeping: false,
+
+            data_changed: false,
+
+            in_flight: 0,
+            waited: 0,
+
+            random: 0,
+        }
+    }
+
+    /// Gets the data map stored by the tile.
+    pub fn data(&self) -> &DataMap {
+        &self.data
+    }
+
+    /// Collects the data map for the game, so that the tile reports the next change to it again.
+    pub fn collect_data(&mut self) -> DataMap {
+        self.data_changed = false;
+
+        self.data.clone()
+    }
+
+    /// Takes the messages to the game that were queued while handling messages.
+    pub fn take_outbox(&mut self) -> Vec<GameMsg> {
+        mem::take(&mut self.outbox)
+    }
+
+    /// Sends a message to the game, or queues it if the tile has no handle to the game.
+    fn send(&mut self, message: GameMsg) -> Result<(), MessagingErr<GameMsg>> {
+        match &self.game {
+            Some(game) => game.send_message(message),
+            None => {
+                self.outbox.push(message);
+
+                Ok(())
+            }
         }
     }
 }
@@ -88,16 +158,28 @@ pub enum TileEntityMsg {
     TransactionResult {
         result: Result<ItemStack, ()>,
     },
+    /// how many of the transactions the tile made are still on their way, so still wait on a result
+    InFlight(usize),
     ExtractRequest {
         requested_from_id: Id,
         requested_from_coord: TileCoord,
     },
     AdjacentState {
-        fulfilled: bool,
+        /// the index of the first adjacency requirement of the script that is not met
+        unmet: Option<usize>,
     },
+    /// the terrain under the tile changed
+    Terrain(Option<Id>),
+    NeighborChanged,
     SetData(DataMap),
     SetDataValue(Id, Data),
     RemoveData(Id),
+    /// take up to the amount of the item from the inventory under the key, and give what was taken to the player
+    TakeItem {
+        key: Id,
+        item: Id,
+        amount: ItemAmount,
+    },
     TakeData(RpcReplyPort<DataMap>),
     GetData(RpcReplyPort<DataMap>),
     GetDataValue(Id, RpcReplyPort<Option<Data>>),
@@ -105,6 +187,120 @@ pub enum TileEntityMsg {
 }
 
 impl TileEntity {
+    /// Tells the game to stop ticking this tile, until something wakes it up.
+    /// A tile waiting on the results of its transactions keeps ticking, so that it can ask the game about them if they take too long.
+    fn sleep(&self, state: &mut TileEntityState) {
+        if state.in_flight > 0 {
+            return;
+        }
+
+        state.sleeping = true;
+
+        state.send(GameMsg::SleepTile(self.coord)).unwrap();
+    }
+
+    /// Tells the game to resume ticking this tile, if it is sleeping.
+    fn wake(&self, state: &mut TileEntityState) {
+        if state.sleeping {
+            state.sleeping = false;
+
+            state.send(GameMsg::WakeTile(self.coord)).unwrap();
+        }
+    }
+
+    /// Tells the game the data of the tile changed, unless it already knows.
+    fn data_changed(&self, state: &mut TileEntityState) {
+        if !state.data_changed {
+            state.data_changed = true;
+
+            state.send(GameMsg::TileDataChanged(self.coord)).unwrap();
+        }
+    }
+
+    /// Reads the data map back from the script's state, and tells the game if the script changed it.
+    fn read_back_data(&self, state: &mut TileEntityState) {
+        let data: DataMap = state
+            .rhai_map
+            .get(RHAI_DATA_MAP_KEY)
+            .cloned()
+            .unwrap()
+            .cast();
+
+        if data != state.data {
+            state.data = data;
+
+            self.data_changed(state);
+        }
+    }
+
+    /// Asks the game to check the tile's surroundings, if its script requires any adjacent tile.
+    fn check_adjacent(&self, state: &mut TileEntityState) {
+        let script = state
+            .data
+            .get(&self.resource_man.registry.data_ids.script)
+            .and_then(Data::as_id)
+            .and_then(|script| self.resource_man.registry.script(*script))
+            .filter(|script| !script.adjacent.is_empty())
+            .cloned();
+
+        if let Some(script) = script {
+            state
+                .send(GameMsg::CheckAdjacent {
+                    script,
+                    coord: self.coord,
+                    self_coord: self.coord,
+                })
+                .unwrap();
+        } else {
+            self.set_adjacent_unmet(state, None);
+        }
+    }
+
+    /// Records which adjacency requirement of the script is not met, so that the info panel can show it.
+    fn set_adjacent_unmet(&self, state: &mut TileEntityState, unmet: Option<usize>) {
+        let fulfilled = unmet.is_none();
+
+        if state.adjacent_fulfilled != fulfilled {
+            self.wake(state);
+        }
+
+        state.adjacent_fulfilled = fulfilled;
+
+        let key = self.resource_man.registry.data_ids.adjacent_unmet;
+        let unmet = unmet.map(|index| index as ItemAmount);
+
+        if state.data.get(&key).and_then(Data::as_amount).cloned() != unmet {
+            if let Some(index) = unmet {
+                state.data.insert(key, Data::Amount(index));
+            } else {
+                state.data.remove(&key);
+            }
+
+            self.data_changed(state);
+        }
+    }
+
+    /// Sends an item to the tile at the given position. The game takes it there, which takes some ticks.
+    fn send_item(&self, state: &mut TileEntityState, coord: TileCoord, shipment: Shipment) {
+        state
+            .send(GameMsg::SendItem {
+                from: self.coord,
+                to: coord,
+                shipment,
+            })
+            .unwrap();
+    }
+
+    /// Records whether the outputs of the tile could be delivered.
+    fn set_output_blocked(&self, state: &mut TileEntityState, blocked: bool) {
+        let key = self.resource_man.registry.data_ids.output_blocked;
+
+        if state.data.get(&key).and_then(Data::as_bool).cloned() != Some(blocked) {
+            state.data.insert(key, Data::Bool(blocked));
+            self.data_changed(state);
+        }
+    }
+
     fn handle_rhai_transaction_result(
         &self,
         state: &mut TileEntityState,
@@ -114,17 +310,17 @@ impl TileEntity {
         root_coord: TileCoord,
         root_id: Id,
         result: rhai::Array,
-    ) -> Option<GameMsg> {
+    ) {
         let ty: ImmutableString = result[0].clone().cast();
 
         match ty.as_str() {
             "pass_on" => {
                 let coord: TileCoord = result[1].clone().cast();
 
-                send_to_tile(
+                self.send_item(
                     state,
                     coord,
-                    Transaction {
+                    Shipment {
                         stack,
                         source_id: self.id,
                         source_coord: self.coord,
@@ -132,16 +328,14 @@ impl TileEntity {
                         root_coord,
                     },
                 );
-
-                return Some(GameMsg::RecordTransaction(stack, source_coord, self.coord));
             }
             "proxy" => {
                 let coord: TileCoord = result[1].clone().cast();
 
-                send_to_tile(
+                self.send_item(
                     state,
                     coord,
-                    Transaction {
+                    Shipment {
                         stack,
                         source_id,
                         source_coord,
@@ -163,13 +357,11 @@ impl TileEntity {
                         }),
                     },
                 );
-
-                return Some(GameMsg::RecordTransaction(stack, source_coord, self.coord));
             }
-            _ => (),
+            _ => {
+                send_to_tile(state, root_coord, TransactionResult { result: Err(()) });
+            }
         }
-
-        None
     }
 
     fn handle_rhai_result(&self, state: &mut TileEntityState, result: rhai::Array) {
@@ -177,28 +369,48 @@ impl TileEntity {
 
         match ty.as_str() {
             "make_transaction" => {
-                let coord: TileCoord = result[1].clone().cast();
+                if state.in_flight > 0 {
+                    return;
+                }
+
                 let source_id: Id = result[2].clone().cast::<INT>().into();
                 let source_coord: TileCoord = result[3].clone().cast();
 
-                let stacks: Vec<ItemStack> = result[4]
-                    .clone()
-                    .try_cast()
-                    .unwrap_or_else(|| result[4].clone().into_typed_array().unwrap());
-
-                for stack in stacks {
-                    send_to_tile(
-                        state,
-                        coord,
-                        Transaction {
-                            stack,
-                            source_coord,
-                            source_id,
-                            root_coord: source_coord,
-                            root_id: source_id,
-                        },
+                // the target and stacks, optionally followed by more targets and their stacks
+                let deliveries = [&result[1]]
+                    .into_iter()
+                    .chain(result.iter().skip(5).step_by(2))
+                    .zip(
+                        [&result[4]]
+                            .into_iter()
+                            .chain(result.iter().skip(6).step_by(2)),
                     );
+
+                for (coord, stacks) in deliveries {
+                    let coord: TileCoord = coord.clone().cast();
+                    let stacks: Vec<ItemStack> = stacks
+                        .clone()
+                        .try_cast()
+                        .unwrap_or_else(|| stacks.clone().into_typed_array().unwrap());
+
+                    for stack in stacks {
+                        self.send_item(
+                            state,
+                            coord,
+                            Shipment {
+                                stack,
+                                source_coord,
+                                source_id,
+                                root_coord: source_coord,
+                                root_id: source_id,
+                            },
+                        );
+
+                        state.in_flight += 1;
+                    }
                 }
+
+                state.waited = 0;
             }
             "make_extract_request" => {
                 let coord: TileCoord = result[1].clone().cast();
@@ -214,6 +426,9 @@ impl TileEntity {
                     },
                 );
             }
+            "idle" => {
+                self.sleep(state);
+            }
             _ => (),
         }
     }
@@ -226,7 +441,7 @@ impl TileEntity {
         source_id: Id,
         root_coord: TileCoord,
         root_id: Id,
-    ) -> Option<GameMsg> {
+    ) {
         let tile = self.resource_man.registry.tile(self.id).unwrap();
 
         if let Some((ast, default_scope)) = tile
@@ -262,21 +477,20 @@ impl TileEntity {
                     ("source_id".into(), Dynamic::from_int(source_id.into())),
                     ("root_coord".into(), Dynamic::from(root_coord)),
                     ("root_id".into(), Dynamic::from_int(root_id.into())),
-                    ("random".into(), Dynamic::from_int(random())),
+                    (
+                        "random".into(),
+                        Dynamic::from_int(random(&mut state.random)),
+                    ),
                     ("stack".into(), Dynamic::from(stack)),
+                    ("terrain".into(), rhai_terrain(state.terrain)),
                 ]),),
             );
 
             state.rhai_map = rhai_state.take().cast::<rhai::Map>();
-            state.data = state
-                .rhai_map
-                .get(RHAI_DATA_MAP_KEY)
-                .cloned()
-                .unwrap()
-                .cast();
+            self.read_back_data(state);
 
             if let Some(result) = result.ok().and_then(|v| v.try_cast::<rhai::Array>()) {
-                return self.handle_rhai_transaction_result(
+                self.handle_rhai_transaction_result(
                     state,
                     stack,
                     source_coord,
@@ -286,65 +500,58 @@ impl TileEntity {
                     result,
                 );
             } else {
-                send_to_tile(state, source_coord, TransactionResult { result: Err(()) });
+                send_to_tile(state, root_coord, TransactionResult { result: Err(()) });
             };
+        } else {
+            send_to_tile(state, root_coord, TransactionResult { result: Err(()) });
         }
-
-        None
     }
 }
 
-#[async_trait::async_trait]
-impl Actor for TileEntity {
-    type Msg = TileEntityMsg;
-    type State = TileEntityState;
-    type Arguments = (ActorRef<GameMsg>,);
-
-    async fn pre_start(
-        &self,
-        _myself: ActorRef<Self::Msg>,
-        args: Self::Arguments,
-    ) -> Result<Self::State, ActorProcessingErr> {
-        Ok(TileEntityState::new(args.0))
-    }
+impl TileEntity {
+    /// Handles a message sent to the tile entity, whether it runs as an actor or is run by the game directly.
+    pub fn handle_message(&self, state: &mut TileEntityState, message: TileEntityMsg) {
+        if !matches!(
+            message,
+            Tick { .. }
+                | AdjacentState { .. }
+                | NeighborChanged
+                | TakeData(_)
+                | GetData(_)
+                | GetDataValue(..)
+                | GetDataWithCoord(_)
+        ) {
+            self.wake(state);
+        }
 
-    async fn handle(
-        &self,
-        _myself: ActorRef<Self::Msg>,
-        message: Self::Msg,
-        state: &mut Self::State,
-    ) -> Result<(), ActorProcessingErr> {
         match message {
             Tick { tick_count } => {
-                let tile = self.resource_man.registry.tile(self.id).unwrap();
+                state.sleeping = false;
+                state.random = seed(tick_count, self.coord);
 
-                if tick_count % 10 == 0 {
-                    if let Some(script) = state
-                        .data
-                        .get(&self.resource_man.registry.data_ids.script)
-                        .and_then(Data::as_id)
-                    {
-                        if let Some(script) = self.resource_man.registry.script(*script).cloned() {
-                            state
-                                .game
-                                .send_message(GameMsg::CheckAdjacent {
-                                    script,
-                                    coord: self.coord,
-                                    self_coord: self.coord,
-                                })
-                                .unwrap();
-                        }
+                if state.in_flight > 0 {
+                    state.waited += 1;
+
+                    if state.waited >= TRANSACTION_TIMEOUT {
+                        state.waited = 0;
+
+                        state.send(GameMsg::CountInFlight(self.coord)).unwrap();
                     }
                 }
 
+                let tile = self.resource_man.registry.tile(self.id).unwrap();
+
                 if !state.adjacent_fulfilled {
-                    return Ok(());
+                    self.sleep(state);
+
+                    return;
                 }
 
                 if let Some((ast, default_scope)) = tile
                     .function
                     .as_ref()
                     .and_then(|v| self.resource_man.functions.get(v))
+                    .filter(|(ast, _)| has_fn(ast, "handle_tick"))
                 {
                     let scope = state
                         .scope
@@ -368,21 +575,22 @@ impl Actor for TileEntity {
                             ),
                             ("coord".into(), Dynamic::from(self.coord)),
                             ("id".into(), Dynamic::from_int(self.id.into())),
-                            ("random".into(), Dynamic::from_int(random())),
+                            (
+                                "random".into(),
+                                Dynamic::from_int(random(&mut state.random)),
+                            ),
+                            ("terrain".into(), rhai_terrain(state.terrain)),
                         ]),),
                     );
 
                     state.rhai_map = rhai_state.take().cast::<rhai::Map>();
-                    state.data = state
-                        .rhai_map
-                        .get(RHAI_DATA_MAP_KEY)
-                        .cloned()
-                        .unwrap()
-                        .cast();
+                    self.read_back_data(state);
 
                     if let Some(result) = result.ok().and_then(|v| v.try_cast::<rhai::Array>()) {
                         self.handle_rhai_result(state, result);
                     }
+                } else {
+                    self.sleep(state);
                 }
             }
             Transaction {
@@ -392,13 +600,17 @@ impl Actor for TileEntity {
                 root_coord,
                 root_id,
             } => {
-                if let Some(record) =
-                    self.transaction(state, stack, source_coord, source_id, root_coord, root_id)
-                {
-                    state.game.send_message(record).unwrap();
-                }
+                self.transaction(state, stack, source_coord, source_id, root_coord, root_id);
+            }
+            InFlight(count) => {
+                state.in_flight = count;
+                state.waited = 0;
             }
             TransactionResult { result } => {
+                state.in_flight = state.in_flight.saturating_sub(1);
+                state.send(GameMsg::TransactionSettled(self.coord)).unwrap();
+                self.set_output_blocked(state, result.is_err());
+
                 if let Ok(transferred) = result {
                     let tile = self.resource_man.registry.tile(self.id).unwrap();
 
@@ -429,29 +641,63 @@ impl Actor for TileEntity {
                                 ),
                                 ("coord".into(), Dynamic::from(self.coord)),
                                 ("id".into(), Dynamic::from_int(self.id.into())),
-                                ("random".into(), Dynamic::from_int(random())),
+                                (
+                                    "random".into(),
+                                    Dynamic::from_int(random(&mut state.random)),
+                                ),
                                 ("transferred".into(), Dynamic::from(transferred)),
+                                ("terrain".into(), rhai_terrain(state.terrain)),
                             ]),),
                         );
 
                         state.rhai_map = rhai_state.take().cast::<rhai::Map>();
-                        state.data = state
-                            .rhai_map
-                            .get(RHAI_DATA_MAP_KEY)
-                            .cloned()
-                            .unwrap()
-                            .cast();
+                        self.read_back_data(state);
                     }
                 }
             }
             SetData(data) => {
                 state.data = data;
+                self.data_changed(state);
+
+                self.check_adjacent(state);
             }
             SetDataValue(key, value) => {
                 state.data.insert(key, value);
+                self.data_changed(state);
+
+                if key == self.resource_man.registry.data_ids.script {
+                    state
+                        .data
+                        .remove(&self.resource_man.registry.data_ids.progress);
+
+                    self.check_adjacent(state);
+                }
+            }
+            TakeItem { key, item, amount } => {
+                let taken = state
+                    .data
+                    .get_mut(&key)
+                    .and_then(Data::as_inventory_mut)
+                    .and_then(|inventory| inventory.take(item, amount))
+                    .filter(|taken| *taken > 0);
+
+                if let Some(taken) = taken {
+                    self.data_changed(state);
+
+                    state
+                        .send(GameMsg::AddItem {
+                            key: self.resource_man.registry.data_ids.player_inventory,
+                            item,
+                            amount: taken,
+                        })
+                        .unwrap();
+                }
             }
             TakeData(reply) => {
                 reply.send(mem::take(&mut state.data)).unwrap();
+                self.data_changed(state);
+
+                self.check_adjacent(state);
             }
             GetData(reply) => {
                 reply.send(state.data.clone()).unwrap();
@@ -460,10 +706,19 @@ impl Actor for TileEntity {
                 reply.send(state.data.get(&key).cloned()).unwrap();
             }
             GetDataWithCoord(reply) => {
-                reply.send((self.coord, state.data.clone())).unwrap();
+                reply.send((self.coord, state.collect_data())).unwrap();
             }
             RemoveData(key) => {
                 state.data.remove(&key);
+                self.data_changed(state);
+
+                if key == self.resource_man.registry.data_ids.script {
+                    state
+                        .data
+                        .remove(&self.resource_man.registry.data_ids.progress);
+
+                    self.check_adjacent(state);
+                }
             }
             ExtractRequest {
                 requested_from_id,
@@ -498,7 +753,10 @@ impl Actor for TileEntity {
                             ),
                             ("coord".into(), Dynamic::from(self.coord)),
                             ("id".into(), Dynamic::from_int(self.id.into())),
-                            ("random".into(), Dynamic::from_int(random())),
+                            (
+                                "random".into(),
+                                Dynamic::from_int(random(&mut state.random)),
+                            ),
                             (
                                 "requested_from_coord".into(),
                                 Dynamic::from(requested_from_coord),
@@ -511,32 +769,56 @@ impl Actor for TileEntity {
                     );
 
                     state.rhai_map = rhai_state.take().cast::<rhai::Map>();
-                    state.data = state
-                        .rhai_map
-                        .get(RHAI_DATA_MAP_KEY)
-                        .cloned()
-                        .unwrap()
-                        .cast();
+                    self.read_back_data(state);
 
                     if let Some(result) = result.ok().and_then(|v| v.try_cast::<rhai::Array>()) {
                         self.handle_rhai_result(state, result);
                     }
                 }
             }
-            AdjacentState { fulfilled } => {
-                state.adjacent_fulfilled = fulfilled;
+            AdjacentState { unmet } => {
+                self.set_adjacent_unmet(state, unmet);
+            }
+            NeighborChanged => {
+                self.check_adjacent(state);
+            }
+            Terrain(terrain) => {
+                state.terrain = terrain;
+
+                self.check_adjacent(state);
             }
         }
+    }
+}
+
+#[async_trait::async_trait]
+impl Actor for TileEntity {
+    type Msg = TileEntityMsg;
+    type State = TileEntityState;
+    type Arguments = (ActorRef<GameMsg>,);
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(TileEntityState::new(Some(args.0)))
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        self.handle_message(state, message);
 
         Ok(())
     }
 }
 
 fn send_to_tile(state: &mut TileEntityState, coord: TileCoord, message: TileEntityMsg) {
-    match state
-        .game
-        .send_message(GameMsg::ForwardMsgToTile(coord, message))
-    {
+    match state.send(GameMsg::ForwardMsgToTile(coord, message)) {
         Ok(_) => {}
         Err(_) => {
             state.data.clear();
@@ -544,6 +826,28 @@ fn send_to_tile(state: &mut TileEntityState, coord: TileCoord, message: TileEnti
     }
 }
 
-fn random() -> i32 {
-    thread_rng().next_u32() as i32
+/// Checks if the script defines a function with the given name.
+fn has_fn(ast: &AST, name: &str) -> bool {
+    ast.iter_functions().any(|f| f.name == name)
+}
+
+/// Seeds the numbers a tile draws in a tick, so the same map plays out the same way on either engine.
+fn seed(tick_count: TickUnit, coord: TileCoord) -> u64 {
+    let coord = ((coord.q() as u32 as u64) << 32) | coord.r() as u32 as u64;
+
+    let mut seed = (tick_count as u64 ^ coord.wrapping_mul(0x9E3779B97F4A7C15))
+        .wrapping_add(0x9E3779B97F4A7C15);
+    seed = (seed ^ (seed >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    seed = (seed ^ (seed >> 27)).wrapping_mul(0x94D049BB133111EB);
+
+    seed ^ (seed >> 31)
+}
+
+/// Draws the next number from the tile's seed.
+fn random(seed: &mut u64) -> INT {
+    *seed = seed
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+
+    (*seed >> 33) as INT
 }
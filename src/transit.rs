@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use automancy_defs::coord::TileCoord;
+use automancy_defs::hashbrown::HashMap;
+use automancy_defs::id::{Id, IdRaw, Interner};
+use automancy_resources::data::stack::{ItemAmount, ItemStack};
+use automancy_resources::ResourceManager;
+
+use crate::game::TickUnit;
+use crate::tile_entity::TileEntityMsg;
+
+/// How many ticks an item takes to go from a tile to the next, if the tile does not say.
+pub const DEFAULT_TRANSIT_TICKS: TickUnit = 1;
+/// How many items can be on their way from a tile to the next at once, if the tile does not say.
+pub const DEFAULT_TRANSIT_CAPACITY: usize = 4;
+
+/// An item on its way to a tile entity.
+#[derive(Debug, Clone, Copy)]
+pub struct Shipment {
+    pub stack: ItemStack,
+    pub source_coord: TileCoord,
+    pub source_id: Id,
+    pub root_coord: TileCoord,
+    pub root_id: Id,
+}
+
+impl Shipment {
+    /// Makes the transaction the item is delivered as.
+    pub fn into_transaction(self) -> TileEntityMsg {
+        TileEntityMsg::Transaction {
+            stack: self.stack,
+            source_coord: self.source_coord,
+            source_id: self.source_id,
+            root_coord: self.root_coord,
+            root_id: self.root_id,
+        }
+    }
+}
+
+/// The items that are on their way between tiles, by the tiles they go from and to.
+#[derive(Debug, Clone, Default)]
+pub struct Transit {
+    /// the items on each edge, in the order they were sent, with the ticks left until they arrive
+    edges: HashMap<(TileCoord, TileCoord), VecDeque<(TickUnit, Shipment)>>,
+    /// the saved items whose item or tiles are not loaded, kept so that they are saved again
+    unknown: Vec<ShipmentRaw>,
+    /// how many of the items each tile sent itself still wait on a result, by the tile
+    outstanding: HashMap<TileCoord, usize>,
+}
+
+impl Transit {
+    /// Counts the items on their way.
+    pub fn len(&self) -> usize {
+        self.edges.values().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.values().all(VecDeque::is_empty)
+    }
+
+    pub fn clear(&mut self) {
+        self.edges.clear();
+        self.unknown.clear();
+        self.outstanding.clear();
+    }
+
+    /// Records that the tile at the given position sent an item itself, which it gets a result for.
+    pub fn sent(&mut self, root_coord: TileCoord) {
+        *self.outstanding.entry(root_coord).or_default() += 1;
+    }
+
+    /// Records that the tile at the given position got the result of one of the items it sent.
+    pub fn settled(&mut self, root_coord: TileCoord) {
+        if let Some(count) = self.outstanding.get_mut(&root_coord) {
+            *count = count.saturating_sub(1);
+
+            if *count == 0 {
+                self.outstanding.remove(&root_coord);
+            }
+        }
+    }
+
+    /// Forgets the items the tile at the given position sent, since it is gone and their results go nowhere.
+    pub fn forget(&mut self, root_coord: TileCoord) {
+        self.outstanding.remove(&root_coord);
+    }
+
+    /// Counts the items the tile at the given position sent that still wait on a result, wherever they are on their way.
+    pub fn in_flight(&self, root_coord: TileCoord) -> usize {
+        self.outstanding.get(&root_coord).cloned().unwrap_or(0)
+    }
+
+    /// Gets how many items still wait on a result, by the tile that sent them.
+    pub fn outstanding(&self) -> impl Iterator<Item = (TileCoord, usize)> + '_ {
+        self.outstanding
+            .iter()
+            .map(|(coord, count)| (*coord, *count))
+    }
+
+    /// Sends an item from a tile to the next, arriving after the given ticks. Gives the item back if the edge is full.
+    pub fn push(
+        &mut self,
+        from: TileCoord,
+        to: TileCoord,
+        shipment: Shipment,
+        ticks: TickUnit,
+        capacity: usize,
+    ) -> Result<(), Shipment> {
+        let edge = self.edges.entry((from, to)).or_default();
+
+        if edge.len() >= capacity {
+            return Err(shipment);
+        }
+
+        edge.push_back((ticks.max(1), shipment));
+
+        Ok(())
+    }
+
+    /// Moves every item one tick further. Returns the items that arrived, with where they arrived at.
+    pub fn tick(&mut self) -> Vec<(TileCoord, Shipment)> {
+        let mut arrived = vec![];
+
+        for ((from, to), edge) in self.edges.iter_mut() {
+            for (remaining, _) in edge.iter_mut() {
+                *remaining = remaining.saturating_sub(1);
+            }
+
+            while let Some((0, shipment)) = edge.front().cloned() {
+                edge.pop_front();
+
+                arrived.push((*from, *to, shipment));
+            }
+        }
+
+        self.edges.retain(|_, edge| !edge.is_empty());
+
+        arrived.sort_by_key(|(from, to, _)| (from.q(), from.r(), to.q(), to.r()));
+
+        arrived
+            .into_iter()
+            .map(|(_, to, shipment)| (to, shipment))
+            .collect()
+    }
+
+    pub fn to_raw(&self, interner: &Interner) -> Vec<ShipmentRaw> {
+        self.edges
+            .iter()
+            .flat_map(|((from, to), edge)| {
+                edge.iter().flat_map(|(remaining, shipment)| {
+                    Some(ShipmentRaw {
+                        from: *from,
+                        to: *to,
+                        remaining: *remaining,
+                        item: IdRaw::parse(interner.resolve(shipment.stack.item.id)?),
+                        amount: shipment.stack.amount,
+                        source_coord: shipment.source_coord,
+                        source_id: IdRaw::parse(interner.resolve(shipment.source_id)?),
+                        root_coord: shipment.root_coord,
+                        root_id: IdRaw::parse(interner.resolve(shipment.root_id)?),
+                    })
+                })
+            })
+            .chain(self.unknown.iter().cloned())
+            .collect()
+    }
+
+    /// Reads the items on their way back from a saved map. Items whose item or tiles are not loaded are kept as they are.
+    /// Only the items that are on their way again wait on a result, since the ones between tiles when the map was saved are lost.
+    pub fn from_raw(raw: &[ShipmentRaw], resource_man: &ResourceManager) -> Self {
+        let mut transit = Self::default();
+
+        for shipment in raw {
+            let id = |id: &IdRaw| resource_man.interner.get(id.to_string());
+
+            let Some(((item, source_id), root_id)) = id(&shipment.item)
+                .and_then(|item| resource_man.registry.item(item).cloned())
+                .zip(id(&shipment.source_id))
+                .zip(id(&shipment.root_id))
+            else {
+                transit.unknown.push(shipment.clone());
+                continue;
+            };
+
+            transit.sent(shipment.root_coord);
+            transit
+                .edges
+                .entry((shipment.from, shipment.to))
+                .or_default()
+                .push_back((
+                    shipment.remaining,
+                    Shipment {
+                        stack: ItemStack {
+                            item,
+                            amount: shipment.amount,
+                        },
+                        source_coord: shipment.source_coord,
+                        source_id,
+                        root_coord: shipment.root_coord,
+                        root_id,
+                    },
+                ));
+        }
+
+        transit
+    }
+}
+
+/// An item on its way, as saved in a map.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShipmentRaw {
+    pub from: TileCoord,
+    pub to: TileCoord,
+    pub remaining: TickUnit,
+    pub item: IdRaw,
+    pub amount: ItemAmount,
+    pub source_coord: TileCoord,
+    pub source_id: IdRaw,
+    pub root_coord: TileCoord,
+    pub root_id: IdRaw,
+}
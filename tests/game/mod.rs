@@ -0,0 +1,384 @@
+use std::time::Duration;
+
+use ractor::{Actor, ActorRef};
+use tokio::sync::watch;
+
+use automancy::game::{Game, GameMsg, GameSnapshot, PlaceTileResponse, RenderView};
+use automancy::simulation::TickEngine;
+use automancy::tile_entity::{TileEntityMsg, TRANSACTION_TIMEOUT};
+use automancy_defs::coord::TileCoord;
+use automancy_defs::id::Id;
+use automancy_resources::data::stack::ItemStack;
+use automancy_resources::data::Data;
+
+use crate::support::resource_man;
+
+async fn game() -> ActorRef<GameMsg> {
+    game_on(TickEngine::Actors).await
+}
+
+async fn game_on(tick_engine: TickEngine) -> ActorRef<GameMsg> {
+    game_with_snapshot(tick_engine).await.0
+}
+
+/// Starts a game, along with where it publishes its snapshots.
+async fn game_with_snapshot(
+    tick_engine: TickEngine,
+) -> (ActorRef<GameMsg>, watch::Receiver<GameSnapshot>) {
+    let (snapshot, receiver) = watch::channel(GameSnapshot::default());
+
+    let (game, _handle) = Actor::spawn(
+        None,
+        Game {
+            resource_man: resource_man(),
+            tick_engine,
+            save_compression: 0,
+            backup_count: 0,
+            autosave_interval: Duration::ZERO,
+            snapshot,
+        },
+        (),
+    )
+    .await
+    .unwrap();
+
+    (game, receiver)
+}
+
+fn id(name: &str) -> Id {
+    resource_man().interner.get(name).unwrap()
+}
+
+async fn place(game: &ActorRef<GameMsg>, coord: TileCoord, id: Id) -> PlaceTileResponse {
+    game.call(
+        |reply| GameMsg::PlaceTile {
+            coord,
+            id,
+            tile_modifier: 0,
+            record: false,
+            reply: Some(reply),
+        },
+        None,
+    )
+    .await
+    .unwrap()
+    .unwrap()
+}
+
+async fn tile(game: &ActorRef<GameMsg>, coord: TileCoord) -> Option<Id> {
+    game.call(|reply| GameMsg::GetTile(coord, reply), None)
+        .await
+        .unwrap()
+        .unwrap()
+        .map(|(id, _)| id)
+}
+
+/// Gets the value under the key in the data of the tile entity at the given position.
+async fn value(game: &ActorRef<GameMsg>, coord: TileCoord, key: Id) -> Option<Data> {
+    let tile_entity = game
+        .call(|reply| GameMsg::GetTileEntity(coord, reply), None)
+        .await
+        .unwrap()
+        .unwrap()?;
+
+    tile_entity
+        .call(|reply| TileEntityMsg::GetDataValue(key, reply), None)
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+/// Gets the amount the tile entity at the given position holds under the amount key.
+async fn amount(game: &ActorRef<GameMsg>, coord: TileCoord) -> Option<i32> {
+    let key = resource_man().registry.data_ids.amount;
+
+    value(game, coord, key)
+        .await
+        .as_ref()
+        .and_then(Data::as_amount)
+        .cloned()
+}
+
+#[tokio::test]
+async fn test_footprint_blocks_placing() {
+    let game = game().await;
+    let big = id("test:big_storage");
+    let conveyor = id("automancy:conveyor");
+
+    assert!(matches!(
+        place(&game, TileCoord::new(0, 0), big).await,
+        PlaceTileResponse::Placed
+    ));
+
+    // the other positions of the tile are taken
+    assert!(matches!(
+        place(&game, TileCoord::new(1, 0), conveyor).await,
+        PlaceTileResponse::Ignored
+    ));
+
+    // and a tile that would reach over them does not fit either
+    assert!(matches!(
+        place(&game, TileCoord::new(-1, 1), big).await,
+        PlaceTileResponse::Ignored
+    ));
+
+    assert_eq!(tile(&game, TileCoord::new(1, 0)).await, None);
+    assert_eq!(tile(&game, TileCoord::new(-1, 1)).await, None);
+}
+
+#[tokio::test]
+async fn test_footprint_routes_to_the_owner() {
+    let game = game().await;
+    let key = resource_man().registry.data_ids.amount;
+
+    place(&game, TileCoord::new(0, 0), id("test:big_storage")).await;
+
+    game.send_message(GameMsg::ForwardMsgToTile(
+        TileCoord::new(0, 1),
+        TileEntityMsg::SetDataValue(key, Data::Amount(7)),
+    ))
+    .unwrap();
+
+    assert_eq!(amount(&game, TileCoord::new(0, 0)).await, Some(7));
+}
+
+#[tokio::test]
+async fn test_blocked_moves_are_refused() {
+    let game = game().await;
+    let big = id("test:big_storage");
+    let conveyor = id("automancy:conveyor");
+
+    place(&game, TileCoord::new(0, 0), big).await;
+    place(&game, TileCoord::new(2, 0), conveyor).await;
+
+    // moving the tile one to the right would put its footprint on the conveyor
+    game.send_message(GameMsg::MoveTiles(
+        vec![TileCoord::new(0, 0)],
+        TileCoord::new(1, 0),
+        false,
+    ))
+    .unwrap();
+
+    assert_eq!(tile(&game, TileCoord::new(0, 0)).await, Some(big));
+    assert_eq!(tile(&game, TileCoord::new(1, 0)).await, None);
+    assert_eq!(tile(&game, TileCoord::new(2, 0)).await, Some(conveyor));
+
+    // moving it away from the conveyor works, and takes its footprint along
+    game.send_message(GameMsg::MoveTiles(
+        vec![TileCoord::new(0, 0)],
+        TileCoord::new(0, -2),
+        false,
+    ))
+    .unwrap();
+
+    assert_eq!(tile(&game, TileCoord::new(0, 0)).await, None);
+    assert_eq!(tile(&game, TileCoord::new(0, -2)).await, Some(big));
+
+    assert!(matches!(
+        place(&game, TileCoord::new(1, 0), conveyor).await,
+        PlaceTileResponse::Placed
+    ));
+    assert!(matches!(
+        place(&game, TileCoord::new(1, -2), conveyor).await,
+        PlaceTileResponse::Ignored
+    ));
+}
+
+/// Gives an extractor at the given position the coal script, and gets which of its adjacency requirements are not met.
+async fn coal_extractor_unmet(game: &ActorRef<GameMsg>, coord: TileCoord) -> Option<Data> {
+    let data_ids = resource_man().registry.data_ids;
+
+    place(game, coord, id("automancy:basic_extractor")).await;
+
+    game.send_message(GameMsg::ForwardMsgToTile(
+        coord,
+        TileEntityMsg::SetDataValue(data_ids.script, Data::Id(id("automancy:coal"))),
+    ))
+    .unwrap();
+
+    // once the tile has the script, it asked the game to check its neighbors, and once the game answers that, it has told the tile
+    assert!(value(game, coord, data_ids.script).await.is_some());
+    tile(game, coord).await;
+
+    value(game, coord, data_ids.adjacent_unmet).await
+}
+
+#[tokio::test]
+async fn test_footprint_neighbors_count_for_adjacency() {
+    let game = game().await;
+    let data_ids = resource_man().registry.data_ids;
+    let coord = TileCoord::new(0, 0);
+
+    place(&game, coord, id("test:big_machine")).await;
+    set(
+        &game,
+        coord,
+        data_ids.script,
+        Data::Id(id("test:beside_storage")),
+    );
+
+    // the tile's own positions are not its neighbors
+    assert!(value(&game, coord, data_ids.script).await.is_some());
+    tile(&game, coord).await;
+
+    assert!(matches!(
+        value(&game, coord, data_ids.adjacent_unmet).await,
+        Some(Data::Amount(0))
+    ));
+
+    // a storage next to one of its other positions, but not next to the tile's own, is a neighbor
+    place(&game, TileCoord::new(2, 0), id("automancy:small_storage")).await;
+
+    assert!(value(&game, coord, data_ids.script).await.is_some());
+    tile(&game, coord).await;
+
+    assert_eq!(value(&game, coord, data_ids.adjacent_unmet).await, None);
+}
+
+#[tokio::test]
+async fn test_terrain_requirements_need_a_generated_map() {
+    let generated = game().await;
+    generated
+        .send_message(GameMsg::CreateMap(
+            resource_man(),
+            "test_terrain_requirements".to_string(),
+            42,
+            String::new(),
+        ))
+        .unwrap();
+
+    // there is no coal deposit under the extractor
+    assert!(matches!(
+        coal_extractor_unmet(&generated, TileCoord::new(0, 0)).await,
+        Some(Data::Amount(0))
+    ));
+
+    // a map without a world generator has no deposits at all, so the extractor does not need one
+    let ungenerated = game().await;
+
+    assert_eq!(
+        coal_extractor_unmet(&ungenerated, TileCoord::new(0, 0)).await,
+        None
+    );
+}
+
+/// Sets the value under the key in the data of the tile entity at the given position.
+fn set(game: &ActorRef<GameMsg>, coord: TileCoord, key: Id, value: Data) {
+    game.send_message(GameMsg::ForwardMsgToTile(
+        coord,
+        TileEntityMsg::SetDataValue(key, value),
+    ))
+    .unwrap();
+}
+
+/// Counts the coal in the queue of the conveyor and the buffer of the storage.
+async fn coal_count(game: &ActorRef<GameMsg>, conveyor: TileCoord, storage: TileCoord) -> i32 {
+    let data_ids = resource_man().registry.data_ids;
+    let coal = id("automancy:coal");
+
+    let queued = match value(game, conveyor, data_ids.queue).await {
+        Some(Data::VecStack(stacks)) => stacks.iter().map(|stack| stack.amount).sum(),
+        _ => 0,
+    };
+    let stored = value(game, storage, data_ids.buffer)
+        .await
+        .as_ref()
+        .and_then(Data::as_inventory)
+        .and_then(|buffer| buffer.get(&coal).cloned())
+        .unwrap_or(0);
+
+    queued + stored
+}
+
+#[tokio::test]
+async fn test_items_passed_on_are_not_sent_twice() {
+    let resource_man = resource_man();
+    let data_ids = resource_man.registry.data_ids;
+    let coal = id("automancy:coal");
+    let (conveyor, merger, storage) = (
+        TileCoord::new(0, 0),
+        TileCoord::new(1, 0),
+        TileCoord::new(2, 0),
+    );
+
+    for tick_engine in [TickEngine::Actors, TickEngine::Batched] {
+        let game = game_on(tick_engine).await;
+
+        place(&game, storage, id("automancy:small_storage")).await;
+        set(&game, storage, data_ids.item, Data::Id(coal));
+        set(&game, storage, data_ids.amount, Data::Amount(100));
+
+        place(&game, merger, id("automancy:merger")).await;
+        set(
+            &game,
+            merger,
+            data_ids.target,
+            Data::Coord(TileCoord::new(1, 0)),
+        );
+
+        // the items take as long as the tile waits to reach the merger, so it asks for a recount while the merger passes them on
+        place(&game, conveyor, id("test:slow_conveyor")).await;
+        set(
+            &game,
+            conveyor,
+            data_ids.target,
+            Data::Coord(TileCoord::new(1, 0)),
+        );
+        set(
+            &game,
+            conveyor,
+            data_ids.queue,
+            Data::VecStack(vec![
+                ItemStack {
+                    item: *resource_man.registry.item(coal).unwrap(),
+                    amount: 1,
+                };
+                4
+            ]),
+        );
+
+        assert_eq!(coal_count(&game, conveyor, storage).await, 4);
+
+        for _ in 0..TRANSACTION_TIMEOUT * 8 {
+            game.send_message(GameMsg::Tick).unwrap();
+            tile(&game, conveyor).await;
+        }
+
+        assert_eq!(
+            coal_count(&game, conveyor, storage).await,
+            4,
+            "{tick_engine:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_render_snapshots_follow_what_the_renderer_applied() {
+    let (game, snapshot) = game_with_snapshot(TickEngine::Actors).await;
+    let coord = TileCoord::new(0, 0);
+    let view = RenderView {
+        center: coord,
+        culling_range: (8, 8),
+    };
+
+    game.send_message(GameMsg::SetRenderView { view, last: None })
+        .unwrap();
+    place(&game, coord, id("automancy:conveyor")).await;
+    game.send_message(GameMsg::Tick).unwrap();
+    tile(&game, coord).await;
+
+    // the tile changed in view, so the next tick published it
+    let render = snapshot.borrow().render.clone().unwrap();
+    assert!(render.full);
+    assert!(render.tiles.contains_key(&coord));
+
+    // once the renderer applied that, only what changed since is published
+    let last = Some((render.version, view));
+    game.send_message(GameMsg::SetRenderView { view, last })
+        .unwrap();
+    tile(&game, coord).await;
+
+    let render = snapshot.borrow().render.clone().unwrap();
+    assert!(!render.full);
+    assert_eq!(render.since, last);
+}
@@ -0,0 +1,159 @@
+use serde_json::{json, Value};
+
+use automancy::inspect::Validator;
+use automancy::map::{LoadReport, MapDump, TileRaw};
+use automancy_defs::coord::TileCoord;
+
+use crate::support::resource_man;
+
+fn tile(id: &str, tile_modifier: i32, data: Value) -> TileRaw {
+    TileRaw {
+        id: id.to_string(),
+        tile_modifier,
+        data: serde_json::from_value(data).unwrap(),
+    }
+}
+
+/// Makes a map as it would be read from disk, with the given tiles.
+fn dump(tiles: Vec<(TileCoord, TileRaw)>) -> MapDump {
+    MapDump {
+        header: serde_json::from_value(json!({})).unwrap(),
+        tiles,
+        terrain: vec![],
+        report: LoadReport::default(),
+    }
+}
+
+/// Checks the map, and gets the messages of the problems at the given position.
+fn problems_at(dump: &MapDump, coord: TileCoord) -> Vec<String> {
+    let resource_man = resource_man();
+
+    Validator::new(&resource_man, dump)
+        .check(dump)
+        .into_iter()
+        .filter(|problem| problem.coord == Some(coord))
+        .map(|problem| problem.message)
+        .collect()
+}
+
+#[test]
+fn test_valid_map_has_no_problems() {
+    let resource_man = resource_man();
+    let dump = dump(vec![
+        (
+            TileCoord::new(0, 0),
+            tile(
+                "automancy:small_storage",
+                0,
+                json!({ "automancy:buffer": { "Inventory": [["automancy:coal", 12]] } }),
+            ),
+        ),
+        (
+            TileCoord::new(1, 0),
+            tile(
+                "automancy:conveyor",
+                0,
+                json!({ "automancy:target": { "Coord": [-1, 0] } }),
+            ),
+        ),
+    ]);
+
+    let problems = Validator::new(&resource_man, &dump).check(&dump);
+
+    assert!(problems.is_empty(), "{problems:?}");
+}
+
+#[test]
+fn test_unknown_ids_are_problems() {
+    let coord = TileCoord::new(0, 0);
+    let dump = dump(vec![
+        (
+            coord,
+            tile(
+                "automancy:small_storage",
+                0,
+                json!({
+                    "automancy:buffer": { "Inventory": [["mod:gear", 3]] },
+                    "mod:links": { "Amount": 1 }
+                }),
+            ),
+        ),
+        (TileCoord::new(5, 5), tile("mod:gadget", 0, json!({}))),
+    ]);
+
+    let mut problems = problems_at(&dump, coord);
+    problems.sort();
+
+    assert_eq!(
+        problems,
+        vec![
+            "automancy:buffer holds unknown item mod:gear".to_string(),
+            "unknown data key mod:links".to_string(),
+        ]
+    );
+    assert_eq!(
+        problems_at(&dump, TileCoord::new(5, 5)),
+        vec!["unknown tile mod:gadget".to_string()]
+    );
+}
+
+#[test]
+fn test_tile_modifier_out_of_range() {
+    let coord = TileCoord::new(0, 0);
+    let dump = dump(vec![(
+        coord,
+        tile("automancy:small_storage", 99, json!({})),
+    )]);
+
+    let problems = problems_at(&dump, coord);
+
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].contains("tile modifier 99"), "{problems:?}");
+}
+
+#[test]
+fn test_targets_must_point_at_tiles() {
+    let machine = TileCoord::new(0, 0);
+    let dump = dump(vec![
+        (
+            machine,
+            tile(
+                "automancy:basic_refinery",
+                0,
+                json!({
+                    "automancy:target": { "Coord": [1, 0] },
+                    "automancy:byproduct_target": { "Coord": [0, 1] }
+                }),
+            ),
+        ),
+        (
+            TileCoord::new(1, 0),
+            tile("automancy:small_storage", 0, json!({})),
+        ),
+    ]);
+
+    // the targets are relative to the tile
+    assert_eq!(
+        problems_at(&dump, machine),
+        vec!["automancy:byproduct_target points at [0, 1], where there is no tile".to_string()]
+    );
+}
+
+#[test]
+fn test_targets_can_point_at_footprints() {
+    let conveyor = TileCoord::new(1, 1);
+    let dump = dump(vec![
+        (TileCoord::new(0, 0), tile("test:big_storage", 0, json!({}))),
+        (
+            conveyor,
+            tile(
+                "automancy:conveyor",
+                0,
+                json!({ "automancy:byproduct_target": { "Coord": [0, -1] } }),
+            ),
+        ),
+    ]);
+
+    // the big storage also takes up 1, 0
+    assert!(problems_at(&dump, conveyor).is_empty());
+}
@@ -0,0 +1,451 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tar::{EntryType, Header};
+use zstd::Encoder;
+
+use automancy::map::{Map, MapInfo, PackedChunk, TileData, MAP_PATH, QUARANTINE_MARK};
+use automancy::migration::MAP_FORMAT_VERSION;
+use automancy_defs::coord::TileCoord;
+use automancy_resources::data::{Data, DataMap, DataMapRaw};
+
+use crate::support::{install_fixture, resource_man, work_dir};
+
+/// Makes a map with a storage tile on it.
+fn map(map_name: &str) -> (Map, TileData) {
+    work_dir();
+
+    let resource_man = resource_man();
+    let coord = TileCoord::new(0, 0);
+
+    let mut map = Map::new_empty(map_name.to_string());
+    map.tiles.insert(
+        coord,
+        (
+            resource_man
+                .interner
+                .get("automancy:small_storage")
+                .unwrap(),
+            0,
+        ),
+    );
+
+    let mut data = DataMap::default();
+    data.insert(resource_man.registry.data_ids.amount, Data::Amount(5));
+
+    (map, TileData::from([(coord, data)]))
+}
+
+fn save(map: &Map, tile_data: &TileData, backup_count: usize, backup_interval: Duration) {
+    map.save(
+        &resource_man().interner,
+        tile_data,
+        0,
+        backup_count,
+        backup_interval,
+    )
+    .unwrap();
+}
+
+fn backups(map_name: &str) -> Vec<usize> {
+    Map::backups(map_name)
+        .into_iter()
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn tick_count(map_name: &str) -> u64 {
+    Map::dump(map_name).unwrap().header.tick_count
+}
+
+/// Gets the names of the maps the files of the given map were moved to.
+fn quarantined(map_name: &str) -> Vec<String> {
+    let prefix = format!("{map_name}{QUARANTINE_MARK}");
+
+    fs::read_dir(MAP_PATH)
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.file_name().to_str().unwrap().to_string())
+        .filter(|name| name.starts_with(&prefix))
+        .collect()
+}
+
+/// Writes an archive with the given files, with their paths written as they are, the way an archive made by hand could have them.
+fn archive(path: &Path, files: &[(&str, &[u8])]) {
+    let encoder = Encoder::new(File::create(path).unwrap(), 0).unwrap();
+    let mut archive = tar::Builder::new(encoder.auto_finish());
+
+    for (name, contents) in files {
+        let mut header = Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        archive.append(&header, *contents).unwrap();
+    }
+
+    archive.into_inner().unwrap();
+}
+
+/// Cuts off the end of the map's tiles, as if the game stopped while writing them.
+fn truncate_tiles(map_name: &str) {
+    let tiles = OpenOptions::new()
+        .write(true)
+        .open(Map::tiles(map_name))
+        .unwrap();
+    let len = tiles.metadata().unwrap().len();
+
+    tiles.set_len(len / 2).unwrap();
+}
+
+#[test]
+fn test_saved_map_loads_back() {
+    let resource_man = resource_man();
+    let (map, tile_data) = map("saved_map_loads_back");
+
+    save(&map, &tile_data, 0, Duration::ZERO);
+
+    let (loaded, loaded_data) = Map::load(&resource_man, "saved_map_loads_back");
+
+    assert_eq!(loaded.tiles, map.tiles);
+    assert_eq!(loaded_data, tile_data);
+}
+
+#[test]
+fn test_chunks_saved_as_they_are_packed_load_back() {
+    let resource_man = resource_man();
+    let (mut map, mut tile_data) = map("chunks_saved_as_packed");
+    let id = |id: &str| resource_man.interner.get(id).unwrap();
+
+    // the chunks far away use ids that the first chunk does not, so their keys are saved with them
+    for q in 1..4 {
+        let coord = TileCoord::new(q * 100, 0);
+
+        map.tiles.insert(coord, (id("automancy:conveyor"), 0));
+        map.terrain.insert(coord, id("automancy:coal_deposit"));
+        tile_data.insert(coord, DataMap::default());
+    }
+
+    save(&map, &tile_data, 0, Duration::ZERO);
+
+    let (loaded, loaded_data) = Map::load(&resource_man, "chunks_saved_as_packed");
+
+    assert_eq!(loaded.tiles, map.tiles);
+    assert_eq!(loaded.terrain, map.terrain);
+    assert_eq!(loaded_data, tile_data);
+}
+
+#[test]
+fn test_v3_tiles_are_read() {
+    work_dir();
+
+    let map_name = "v3_tiles";
+    fs::create_dir_all(Map::path(map_name)).unwrap();
+    fs::write(
+        Map::header(map_name),
+        r#"{"format_version": 3, "generation": 7, "tile_count": 0}"#,
+    )
+    .unwrap();
+
+    // format version 3 has every key before the chunks
+    let mut encoder = Encoder::new(File::create(Map::tiles(map_name)).unwrap(), 0).unwrap();
+    bincode::serialize_into(
+        &mut encoder,
+        &(7u64, vec!["automancy:coal_deposit".to_string()], 1u64),
+    )
+    .unwrap();
+    bincode::serialize_into(
+        &mut encoder,
+        &PackedChunk {
+            tiles: Vec::new(),
+            terrain: vec![(TileCoord::new(3, -2), 0)],
+        },
+    )
+    .unwrap();
+    encoder.finish().unwrap();
+
+    let dump = Map::dump(map_name).unwrap();
+
+    assert!(dump.report.is_complete(), "{:?}", dump.report.errors);
+    assert_eq!(
+        dump.terrain,
+        vec![(TileCoord::new(3, -2), "automancy:coal_deposit".to_string())]
+    );
+}
+
+#[test]
+fn test_backups_wait_for_the_interval() {
+    let (map, tile_data) = map("backups_wait_for_the_interval");
+    let hour = Duration::from_secs(60 * 60);
+
+    // there is nothing to back up yet the first time
+    save(&map, &tile_data, 3, hour);
+    assert!(backups("backups_wait_for_the_interval").is_empty());
+
+    save(&map, &tile_data, 3, hour);
+    assert_eq!(backups("backups_wait_for_the_interval"), vec![0]);
+
+    // the backup is newer than the interval
+    save(&map, &tile_data, 3, hour);
+    assert_eq!(backups("backups_wait_for_the_interval"), vec![0]);
+
+    // without an interval every save backs up, keeping as many backups as asked
+    for _ in 0..4 {
+        save(&map, &tile_data, 3, Duration::ZERO);
+    }
+    assert_eq!(backups("backups_wait_for_the_interval"), vec![0, 1, 2]);
+}
+
+#[test]
+fn test_restore_backup() {
+    let (mut map, tile_data) = map("restore_backup");
+
+    map.tick_count = 1;
+    save(&map, &tile_data, 3, Duration::ZERO);
+    map.tick_count = 2;
+    save(&map, &tile_data, 3, Duration::ZERO);
+
+    assert_eq!(tick_count("restore_backup"), 2);
+
+    Map::restore_backup("restore_backup", 0, 3).unwrap();
+    assert_eq!(tick_count("restore_backup"), 1);
+
+    // the map as it was became the newest backup, so restoring it undoes the restore
+    Map::restore_backup("restore_backup", 0, 3).unwrap();
+    assert_eq!(tick_count("restore_backup"), 2);
+
+    assert!(Map::restore_backup("restore_backup", 5, 3).is_err());
+}
+
+#[test]
+fn test_header_from_another_save_is_noticed() {
+    let (mut map, tile_data) = map("header_from_another_save");
+    let header = Map::header("header_from_another_save");
+    let old_header = work_dir().join("old_header.json");
+
+    save(&map, &tile_data, 0, Duration::ZERO);
+    fs::copy(&header, &old_header).unwrap();
+
+    map.tick_count = 2;
+    save(&map, &tile_data, 0, Duration::ZERO);
+
+    // as if the game stopped after replacing the tiles, but before replacing the header
+    fs::copy(&old_header, &header).unwrap();
+
+    let dump = Map::dump("header_from_another_save").unwrap();
+
+    assert_eq!(dump.report.read, 1);
+    assert!(dump
+        .report
+        .errors
+        .iter()
+        .any(|error| error.contains("different saves")));
+}
+
+#[test]
+fn test_damaged_map_is_moved_aside() {
+    let resource_man = resource_man();
+    let (map, tile_data) = map("damaged_map");
+
+    save(&map, &tile_data, 0, Duration::ZERO);
+    truncate_tiles("damaged_map");
+
+    let (loaded, _) = Map::load(&resource_man, "damaged_map");
+    assert!(loaded.tiles.is_empty());
+
+    let moved = quarantined("damaged_map");
+    assert_eq!(moved.len(), 1);
+    assert!(Map::is_quarantined(&moved[0]));
+    assert!(Map::header(&moved[0]).exists());
+    assert!(Map::tiles(&moved[0]).exists());
+    assert!(!Map::header("damaged_map").exists());
+    assert!(!Map::tiles("damaged_map").exists());
+
+    let report = Map::dump(&moved[0]).unwrap().report;
+    assert!(!report.is_complete());
+}
+
+#[test]
+fn test_damaged_map_is_moved_aside_once() {
+    let resource_man = resource_man();
+    let (map, tile_data) = map("damaged_map_once");
+
+    save(&map, &tile_data, 0, Duration::ZERO);
+    truncate_tiles("damaged_map_once");
+    Map::load(&resource_man, "damaged_map_once");
+
+    let moved = quarantined("damaged_map_once");
+    assert_eq!(moved.len(), 1);
+
+    let err_map_name = &moved[0];
+    let (loaded, _) = Map::load(&resource_man, err_map_name);
+    assert_eq!(loaded.map_name, *err_map_name);
+
+    // the damaged files stay where they were moved, rather than being moved again
+    assert!(quarantined(err_map_name).is_empty());
+    assert!(Map::header(err_map_name).exists());
+    assert!(Map::tiles(err_map_name).exists());
+}
+
+#[test]
+fn test_too_new_map_is_left_alone() {
+    let resource_man = resource_man();
+    let (map, tile_data) = map("too_new_map");
+
+    save(&map, &tile_data, 0, Duration::ZERO);
+
+    let path = Map::header("too_new_map");
+    let mut header: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    header["format_version"] = Value::from(MAP_FORMAT_VERSION + 1);
+    fs::write(&path, serde_json::to_string(&header).unwrap()).unwrap();
+    let saved_header = fs::read(&path).unwrap();
+
+    // it is still listed, so that the player can tell why it does not open
+    let (header, time) = Map::read_header(&resource_man, "too_new_map").unwrap();
+    assert!(MapInfo::from_header(&header, time).is_too_new());
+
+    let (loaded, loaded_data) = Map::load(&resource_man, "too_new_map");
+    assert!(loaded.tiles.is_empty());
+    assert!(loaded_data.is_empty());
+
+    assert!(quarantined("too_new_map").is_empty());
+    assert!(Map::tiles("too_new_map").exists());
+
+    // saving what was loaded would lose everything on the map
+    assert!(loaded
+        .save(&resource_man.interner, &loaded_data, 0, 0, Duration::ZERO)
+        .is_err());
+    assert_eq!(fs::read(&path).unwrap(), saved_header);
+}
+
+#[test]
+fn test_unknown_tile_data_is_saved_back() {
+    let resource_man = resource_man();
+    let interner = &resource_man.interner;
+    let storage = TileCoord::new(0, 0);
+
+    install_fixture("migration/fixtures/v2", "unknown_tile_data");
+
+    let (map, mut tile_data) = Map::load(&resource_man, "unknown_tile_data");
+
+    // the items that are loaded are usable, and the rest is kept aside
+    let buffer = tile_data[&storage]
+        .get(&resource_man.registry.data_ids.buffer)
+        .and_then(Data::as_inventory)
+        .unwrap();
+    assert_eq!(buffer.len(), 1);
+    assert_eq!(
+        serde_json::to_value(&map.unknown_tile_data[&storage]).unwrap(),
+        json!({ "automancy:buffer": { "Inventory": [["mod:gear", 3]] } })
+    );
+
+    tile_data
+        .get_mut(&storage)
+        .unwrap()
+        .get_mut(&resource_man.registry.data_ids.buffer)
+        .and_then(Data::as_inventory_mut)
+        .unwrap()
+        .insert(interner.get("automancy:coal").unwrap(), 20);
+
+    save(&map, &tile_data, 0, Duration::ZERO);
+
+    let dump = Map::dump("unknown_tile_data").unwrap();
+    let (_, tile) = dump
+        .tiles
+        .iter()
+        .find(|(coord, _)| *coord == storage)
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(&tile.data).unwrap(),
+        json!({
+            "automancy:buffer": { "Inventory": [["automancy:coal", 20], ["mod:gear", 3]] }
+        })
+    );
+}
+
+#[test]
+fn test_unknown_map_data_is_saved_back() {
+    let resource_man = resource_man();
+    let (mut map, tile_data) = map("unknown_map_data");
+    let coal = resource_man.interner.get("automancy:coal").unwrap();
+
+    let mut inventory = Data::new_inventory();
+    inventory.as_inventory_mut().unwrap().insert(coal, 4);
+    map.data
+        .insert(resource_man.registry.data_ids.player_inventory, inventory);
+    map.unknown_data = serde_json::from_value::<DataMapRaw>(json!({
+        "automancy:player_inventory": { "Inventory": [["mod:gear", 2]] },
+        "mod:research": { "Amount": 3 }
+    }))
+    .unwrap();
+
+    save(&map, &tile_data, 0, Duration::ZERO);
+
+    assert_eq!(
+        serde_json::to_value(&Map::dump("unknown_map_data").unwrap().header.data).unwrap(),
+        json!({
+            "automancy:player_inventory": { "Inventory": [["automancy:coal", 4], ["mod:gear", 2]] },
+            "mod:research": { "Amount": 3 }
+        })
+    );
+
+    let (loaded, _) = Map::load(&resource_man, "unknown_map_data");
+
+    assert_eq!(loaded.data, map.data);
+    assert_eq!(
+        serde_json::to_value(&loaded.unknown_data).unwrap(),
+        serde_json::to_value(&map.unknown_data).unwrap()
+    );
+}
+
+#[test]
+fn test_exported_map_imports() {
+    let resource_man = resource_man();
+    let (map, tile_data) = map("exported_map");
+    let file = work_dir().join("imported_map.tar.zst");
+
+    save(&map, &tile_data, 0, Duration::ZERO);
+    Map::export("exported_map", &file).unwrap();
+
+    let map_name = Map::import(&file).unwrap();
+    assert_eq!(map_name, "imported_map");
+
+    let (imported, imported_data) = Map::load(&resource_man, &map_name);
+    assert_eq!(imported.tiles, map.tiles);
+    assert_eq!(imported_data, tile_data);
+
+    // a map that is already there is never replaced
+    assert_eq!(Map::import(&file).unwrap(), "imported_map_2");
+    assert_eq!(Map::import(&file).unwrap(), "imported_map_3");
+    assert!(Map::header("imported_map").exists());
+}
+
+#[test]
+fn test_import_refuses_paths_outside_the_map() {
+    let header: &[u8] = br#"{"format_version": 3}"#;
+    let escaped = work_dir().join("escaped_absolute");
+    let escaped = escaped.to_str().unwrap();
+
+    for (map_name, path) in [("escape_up", "../escaped_up"), ("escape_absolute", escaped)] {
+        let file = work_dir().join(format!("{map_name}.tar.zst"));
+        archive(
+            &file,
+            &[("header.json", header), (path, b"escaped".as_slice())],
+        );
+
+        let err = Map::import(&file).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{err}");
+
+        // what was unpacked before the bad path is cleaned up as well
+        assert!(!Map::path(map_name).exists());
+    }
+
+    assert!(!Path::new(MAP_PATH).join("escaped_up").exists());
+    assert!(!Path::new(escaped).exists());
+}
@@ -0,0 +1,224 @@
+use std::fs::{read_to_string, File};
+
+use serde_json::{json, Value};
+use zstd::Decoder;
+
+use automancy::map::{Map, MapHeader, SerdeTile};
+use automancy::migration::{
+    check_version, format_version, MigrationError, MAP_FORMAT_VERSION, PACKED_FORMAT_VERSION,
+};
+use automancy_defs::coord::TileCoord;
+use automancy_resources::data::Data;
+
+use crate::support::{install_fixture, resource_man};
+
+/// Gets the folder of the fixture map saved in the given format version.
+fn fixture_dir(version: u32) -> String {
+    format!(
+        "{}/tests/migration/fixtures/v{version}",
+        env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+/// Reads the header and tiles of a fixture map saved in a format version that saved tiles as JSON, as they are on disk.
+fn fixture(version: u32) -> (Value, Value) {
+    let dir = fixture_dir(version);
+
+    let header = serde_json::from_str(&read_to_string(format!("{dir}/header.json")).unwrap());
+    let tiles = serde_json::from_reader(
+        Decoder::new(File::open(format!("{dir}/tiles.zst")).unwrap()).unwrap(),
+    );
+
+    (header.unwrap(), tiles.unwrap())
+}
+
+#[test]
+fn test_fixtures_are_read_as_saved() {
+    for version in 0..PACKED_FORMAT_VERSION {
+        let (header, tiles) = fixture(version);
+
+        assert_eq!(format_version(&header), version);
+        assert_eq!(check_version(&header).unwrap(), version);
+
+        let header: MapHeader = serde_json::from_value(header).unwrap();
+        let tiles: Vec<(TileCoord, SerdeTile)> = serde_json::from_value(tiles).unwrap();
+
+        assert_eq!(header.tile_count, tiles.len() as u64);
+    }
+}
+
+#[test]
+fn test_newer_format_is_rejected() {
+    let (mut header, _) = fixture(PACKED_FORMAT_VERSION - 1);
+    header["format_version"] = Value::from(MAP_FORMAT_VERSION + 1);
+
+    assert!(matches!(
+        check_version(&header),
+        Err(MigrationError::TooNew(_))
+    ));
+}
+
+#[test]
+fn test_v0_map_dumps() {
+    install_fixture("migration/fixtures/v0", "fixture_v0");
+
+    let dump = Map::dump("fixture_v0").unwrap();
+
+    assert!(dump.report.is_complete(), "{:?}", dump.report.errors);
+    assert_eq!(dump.report.read, 2);
+    assert_eq!(dump.header.seed, None);
+    assert!(dump.header.transit.is_empty());
+    assert!(dump.terrain.is_empty());
+
+    let mut tiles = dump.tiles;
+    tiles.sort_by_key(|(coord, _)| (coord.q(), coord.r()));
+
+    let ids = tiles
+        .iter()
+        .map(|(coord, tile)| (*coord, tile.id.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        vec![
+            (TileCoord::new(0, 0), "automancy:small_storage"),
+            (TileCoord::new(1, 0), "automancy:conveyor"),
+        ]
+    );
+
+    assert_eq!(
+        serde_json::to_value(&tiles[0].1.data).unwrap(),
+        json!({
+            "automancy:item": { "Id": "automancy:coal" },
+            "automancy:buffer": { "Inventory": [["automancy:coal", 12]] }
+        })
+    );
+    assert_eq!(
+        serde_json::to_value(&dump.header.data).unwrap(),
+        json!({
+            "automancy:player_inventory": { "Inventory": [["automancy:coal", 4]] }
+        })
+    );
+}
+
+#[test]
+fn test_v1_map_dumps() {
+    install_fixture("migration/fixtures/v1", "fixture_v1");
+
+    let dump = Map::dump("fixture_v1").unwrap();
+
+    assert!(dump.report.is_complete(), "{:?}", dump.report.errors);
+    assert_eq!(dump.report.read, 1);
+    assert_eq!(dump.header.seed, Some(42));
+    assert_eq!(dump.header.tick_count, 3600);
+    assert_eq!(dump.header.description, "a fixture");
+
+    let (coord, tile) = &dump.tiles[0];
+    assert_eq!(*coord, TileCoord::new(3, -2));
+    assert_eq!(tile.id, "automancy:basic_extractor");
+    assert_eq!(
+        serde_json::to_value(&tile.data).unwrap(),
+        json!({
+            "automancy:script": { "Id": "automancy:coal" },
+            "automancy:output_buffer": { "VecStack": [["automancy:coal", 8]] }
+        })
+    );
+
+    assert_eq!(
+        dump.terrain,
+        vec![(TileCoord::new(3, -2), "automancy:coal_deposit".to_string())]
+    );
+}
+
+#[test]
+fn test_v2_map_dumps() {
+    install_fixture("migration/fixtures/v2", "fixture_v2_dump");
+
+    let dump = Map::dump("fixture_v2_dump").unwrap();
+
+    assert!(dump.report.is_complete(), "{:?}", dump.report.errors);
+    assert_eq!(dump.report.read, 3);
+    assert_eq!(dump.header.format_version, PACKED_FORMAT_VERSION);
+    assert_eq!(dump.header.seed, Some(7));
+
+    let mut tiles = dump.tiles;
+    tiles.sort_by_key(|(coord, _)| (coord.q(), coord.r()));
+
+    let ids = tiles
+        .iter()
+        .map(|(coord, tile)| (*coord, tile.id.as_str(), tile.tile_modifier))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        vec![
+            (TileCoord::new(0, 0), "automancy:small_storage", 0),
+            (TileCoord::new(1, 0), "automancy:conveyor", 0),
+            (TileCoord::new(40, 0), "mod:gadget", 1),
+        ]
+    );
+
+    // the keys are written out as they were saved, whether they are loaded or not
+    assert_eq!(
+        serde_json::to_value(&tiles[0].1.data).unwrap(),
+        json!({
+            "automancy:buffer": { "Inventory": [["automancy:coal", 12], ["mod:gear", 3]] }
+        })
+    );
+    assert_eq!(
+        serde_json::to_value(&tiles[1].1.data).unwrap(),
+        json!({
+            "automancy:target": { "Coord": [-1, 0] }
+        })
+    );
+
+    assert_eq!(
+        dump.terrain,
+        vec![(TileCoord::new(0, 0), "automancy:coal_deposit".to_string())]
+    );
+}
+
+#[test]
+fn test_v2_map_loads() {
+    let resource_man = resource_man();
+    let interner = &resource_man.interner;
+    let data_ids = resource_man.registry.data_ids;
+
+    install_fixture("migration/fixtures/v2", "fixture_v2_load");
+
+    let (map, tile_data) = Map::load(&resource_man, "fixture_v2_load");
+
+    assert_eq!(map.seed, Some(7));
+    assert_eq!(map.tick_count, 1200);
+
+    let storage = TileCoord::new(0, 0);
+    assert_eq!(
+        map.tiles.get(&storage),
+        Some(&(interner.get("automancy:small_storage").unwrap(), 0))
+    );
+    assert_eq!(
+        map.tiles.get(&TileCoord::new(1, 0)),
+        Some(&(interner.get("automancy:conveyor").unwrap(), 0))
+    );
+    assert_eq!(
+        map.terrain.get(&storage),
+        interner.get("automancy:coal_deposit").as_ref()
+    );
+
+    // a tile whose resources are not loaded is kept as it was saved
+    let gadget = map.unknown.get(&TileCoord::new(40, 0)).unwrap();
+    assert_eq!(gadget.id, "mod:gadget");
+    assert_eq!(gadget.tile_modifier, 1);
+
+    let coal = interner.get("automancy:coal").unwrap();
+    let buffer = tile_data[&storage]
+        .get(&data_ids.buffer)
+        .and_then(Data::as_inventory)
+        .unwrap();
+    assert_eq!(buffer.get(&coal), Some(&12));
+
+    let player_inventory = map
+        .data
+        .get(&data_ids.player_inventory)
+        .and_then(Data::as_inventory)
+        .unwrap();
+    assert_eq!(player_inventory.get(&coal), Some(&4));
+}
@@ -0,0 +1,337 @@
+use std::sync::{Arc, Mutex};
+
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+
+use automancy::game::GameMsg;
+use automancy::simulation::{Simulation, MAX_RESOLVE_STEPS};
+use automancy::tile_entity::{TileEntity, TileEntityMsg};
+use automancy_defs::coord::TileCoord;
+use automancy_defs::hashbrown::HashSet;
+use automancy_resources::data::stack::ItemStack;
+use automancy_resources::data::{Data, DataMap};
+use automancy_resources::ResourceManager;
+
+use crate::support::resource_man;
+
+fn tile(resource_man: &Arc<ResourceManager>, id: &str, coord: TileCoord) -> TileEntity {
+    TileEntity {
+        id: resource_man.interner.get(id).unwrap(),
+        coord,
+        tile_modifier: 0,
+        resource_man: resource_man.clone(),
+    }
+}
+
+/// Data that tells tiles apart.
+fn labelled(resource_man: &ResourceManager, label: i32) -> DataMap {
+    let mut data = DataMap::default();
+    data.insert(resource_man.registry.data_ids.amount, Data::Amount(label));
+
+    data
+}
+
+fn label(simulation: &Simulation, coord: TileCoord) -> Option<i32> {
+    let resource_man = resource_man();
+
+    simulation
+        .all_data()
+        .get(&coord)?
+        .get(&resource_man.registry.data_ids.amount)
+        .and_then(Data::as_amount)
+        .cloned()
+}
+
+/// Stands in for the game, writing down every message it gets.
+struct Recorder;
+
+#[async_trait::async_trait]
+impl Actor for Recorder {
+    type Msg = GameMsg;
+    type State = Arc<Mutex<Vec<String>>>;
+    type Arguments = Arc<Mutex<Vec<String>>>;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        log: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(log)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        log: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            // only used to wait for the messages before it
+            GameMsg::GetDataValue(_, reply) => {
+                reply.send(None).unwrap();
+            }
+            message => log.lock().unwrap().push(format!("{message:?}")),
+        }
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_engines_send_the_same_messages() {
+    let resource_man = resource_man();
+    let data_ids = resource_man.registry.data_ids;
+    let coord = TileCoord::new(2, -1);
+
+    let mut data = DataMap::default();
+    data.insert(data_ids.target, Data::Coord(TileCoord::new(1, 0)));
+    data.insert(
+        data_ids.script,
+        Data::Id(resource_man.interner.get("automancy:coal_to_fire").unwrap()),
+    );
+
+    let messages = || {
+        vec![
+            TileEntityMsg::SetData(data.clone()),
+            TileEntityMsg::Tick { tick_count: 0 },
+            TileEntityMsg::RemoveData(data_ids.target),
+            TileEntityMsg::Tick { tick_count: 1 },
+            TileEntityMsg::SetDataValue(data_ids.target, Data::Coord(TileCoord::new(0, 1))),
+            TileEntityMsg::Tick { tick_count: 2 },
+        ]
+    };
+
+    // the tile as its own actor
+    let log = Arc::new(Mutex::new(vec![]));
+    let (game, _) = Actor::spawn(None, Recorder, log.clone()).await.unwrap();
+    let (actor, _) = Actor::spawn(
+        None,
+        tile(&resource_man, "automancy:basic_refinery", coord),
+        (game.clone(),),
+    )
+    .await
+    .unwrap();
+
+    for message in messages() {
+        actor.send_message(message).unwrap();
+    }
+
+    actor
+        .call(TileEntityMsg::GetData, None)
+        .await
+        .unwrap()
+        .unwrap();
+    game.call(|reply| GameMsg::GetDataValue(data_ids.amount, reply), None)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let actors = log.lock().unwrap().clone();
+
+    // the same tile run by the game
+    let mut simulation = Simulation::default();
+    simulation.insert(
+        tile(&resource_man, "automancy:basic_refinery", coord),
+        DataMap::default(),
+    );
+    // a new actor has not reported any change yet
+    simulation.data([coord]);
+
+    let mut batched = vec![];
+
+    for message in messages() {
+        let sent = match message {
+            TileEntityMsg::Tick { tick_count } => simulation.tick(tick_count, &HashSet::new()),
+            message => simulation.send(coord, message),
+        };
+
+        batched.extend(sent.iter().map(|message| format!("{message:?}")));
+    }
+
+    assert!(!actors.is_empty());
+    assert_eq!(actors, batched);
+
+    actor.stop(None);
+    game.stop(None);
+}
+
+#[test]
+fn test_messages_are_resolved_in_position_order() {
+    let resource_man = resource_man();
+    let coords = [
+        TileCoord::new(3, 0),
+        TileCoord::new(-2, 5),
+        TileCoord::new(0, -1),
+        TileCoord::new(0, 2),
+    ];
+
+    let mut simulation = Simulation::default();
+
+    for coord in coords {
+        simulation.insert(
+            tile(&resource_man, "automancy:coal_deposit", coord),
+            DataMap::default(),
+        );
+    }
+
+    let slept = simulation
+        .tick(0, &HashSet::new())
+        .into_iter()
+        .flat_map(|message| match message {
+            GameMsg::SleepTile(coord) => Some(coord),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut sorted = coords.to_vec();
+    sorted.sort_by_key(|coord| (coord.q(), coord.r()));
+
+    assert_eq!(slept, sorted);
+}
+
+#[test]
+fn test_messages_over_the_cap_wait_for_the_next_tick() {
+    let resource_man = resource_man();
+    let count = MAX_RESOLVE_STEPS + 10;
+
+    let mut simulation = Simulation::default();
+
+    for q in 0..count {
+        simulation.insert(
+            tile(
+                &resource_man,
+                "automancy:coal_deposit",
+                TileCoord::new(q as i32, 0),
+            ),
+            DataMap::default(),
+        );
+    }
+
+    // each tile without a function only says that it goes to sleep
+    assert_eq!(simulation.tick(0, &HashSet::new()).len(), MAX_RESOLVE_STEPS);
+
+    let sleeping = (0..count)
+        .map(|q| TileCoord::new(q as i32, 0))
+        .collect::<HashSet<_>>();
+
+    assert_eq!(simulation.tick(1, &sleeping).len(), 10);
+    assert!(simulation.tick(2, &sleeping).is_empty());
+}
+
+#[test]
+fn test_move_all() {
+    let resource_man = resource_man();
+    let a = TileCoord::new(0, 0);
+    let b = TileCoord::new(1, 0);
+    let c = TileCoord::new(2, 0);
+
+    let mut simulation = Simulation::default();
+    simulation.insert(
+        tile(&resource_man, "automancy:coal_deposit", a),
+        labelled(&resource_man, 1),
+    );
+    simulation.insert(
+        tile(&resource_man, "automancy:coal_deposit", b),
+        labelled(&resource_man, 2),
+    );
+
+    // tiles can swap places
+    simulation.move_all(&[(a, b), (b, a)]);
+
+    assert_eq!(label(&simulation, a), Some(2));
+    assert_eq!(label(&simulation, b), Some(1));
+
+    // and replace what is at their destination
+    simulation.move_all(&[(a, b)]);
+
+    assert_eq!(simulation.len(), 1);
+    assert!(!simulation.contains(a));
+    assert_eq!(label(&simulation, b), Some(2));
+
+    // moving nothing leaves the tiles alone
+    simulation.move_all(&[(c, a)]);
+
+    assert_eq!(simulation.len(), 1);
+    assert_eq!(label(&simulation, b), Some(2));
+}
+
+#[test]
+fn test_aliases_route_to_the_owner() {
+    let resource_man = resource_man();
+    let data_ids = resource_man.registry.data_ids;
+    let owner = TileCoord::new(0, 0);
+    let cell = TileCoord::new(0, 1);
+
+    let mut simulation = Simulation::default();
+    simulation.insert(
+        tile(&resource_man, "automancy:coal_deposit", owner),
+        labelled(&resource_man, 1),
+    );
+    simulation.alias(cell, owner);
+
+    simulation.send(
+        cell,
+        TileEntityMsg::SetDataValue(data_ids.amount, Data::Amount(2)),
+    );
+    assert_eq!(label(&simulation, owner), Some(2));
+
+    simulation.unalias(cell);
+
+    simulation.send(
+        cell,
+        TileEntityMsg::SetDataValue(data_ids.amount, Data::Amount(3)),
+    );
+    assert_eq!(label(&simulation, owner), Some(2));
+    assert!(!simulation.contains(cell));
+}
+
+#[test]
+fn test_random_draws_are_the_same_every_run() {
+    let resource_man = resource_man();
+    let coal = resource_man.interner.get("automancy:coal").unwrap();
+    let coords = (0..8).map(|q| TileCoord::new(q * 3, 0)).collect::<Vec<_>>();
+
+    // splitters pick where each item goes at random
+    let run = || {
+        let mut simulation = Simulation::default();
+
+        for coord in &coords {
+            simulation.insert(
+                tile(&resource_man, "automancy:splitter", *coord),
+                DataMap::default(),
+            );
+        }
+
+        let mut sent = vec![];
+
+        for tick_count in 0..4 {
+            sent.extend(simulation.tick(tick_count, &HashSet::new()));
+
+            for coord in &coords {
+                let source_coord = *coord + TileCoord::new(-1, 0);
+
+                sent.extend(simulation.send(
+                    *coord,
+                    TileEntityMsg::Transaction {
+                        stack: ItemStack {
+                            item: *resource_man.registry.item(coal).unwrap(),
+                            amount: 1,
+                        },
+                        source_coord,
+                        source_id: coal,
+                        root_coord: source_coord,
+                        root_id: coal,
+                    },
+                ));
+            }
+        }
+
+        sent.iter()
+            .map(|message| format!("{message:?}"))
+            .collect::<Vec<_>>()
+    };
+
+    let first = run();
+
+    assert!(!first.is_empty());
+    assert_eq!(first, run());
+}
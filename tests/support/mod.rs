@@ -0,0 +1,80 @@
+use std::env::set_current_dir;
+use std::fs::{copy, create_dir_all, read_dir};
+use std::path::Path;
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use tempfile::TempDir;
+
+use automancy::map::Map;
+use automancy_resources::kira::manager::backend::mock::MockBackend;
+use automancy_resources::kira::manager::{AudioManager, AudioManagerSettings};
+use automancy_resources::kira::track::TrackBuilder;
+use automancy_resources::{ResourceManager, RESOURCES_PATH, RESOURCE_MAN};
+
+lazy_static! {
+    static ref RESOURCES: Arc<ResourceManager> = load();
+    static ref WORK_DIR: TempDir = enter_work_dir();
+}
+
+/// Gets the game's resources, shared between the tests.
+pub fn resource_man() -> Arc<ResourceManager> {
+    RESOURCES.clone()
+}
+
+/// Loads the game's tiles, items, tags, scripts and functions, and the tiles only the tests use. The models and audio are skipped, since they need the processed assets.
+fn load() -> Arc<ResourceManager> {
+    let mut audio_man = AudioManager::<MockBackend>::new(AudioManagerSettings::default()).unwrap();
+    let track = audio_man.add_sub_track(TrackBuilder::new()).unwrap();
+
+    let mut resource_man = ResourceManager::new(track);
+
+    let resources = format!("{}/{RESOURCES_PATH}", env!("CARGO_MANIFEST_DIR"));
+    let test_resources = format!("{}/tests/resources", env!("CARGO_MANIFEST_DIR"));
+
+    for dir in read_dir(resources)
+        .unwrap()
+        .chain(read_dir(test_resources).unwrap())
+        .flatten()
+        .map(|v| v.path())
+    {
+        resource_man.load_tiles(&dir).unwrap();
+        resource_man.load_items(&dir).unwrap();
+        resource_man.load_tags(&dir).unwrap();
+        resource_man.load_scripts(&dir).unwrap();
+        resource_man.load_functions(&dir).unwrap();
+    }
+
+    resource_man.ordered_items();
+
+    let resource_man = Arc::new(resource_man);
+    RESOURCE_MAN.write().unwrap().replace(resource_man.clone());
+
+    resource_man
+}
+
+/// Runs the tests from an empty temporary folder, so that the maps they read and write do not touch the game's own.
+fn enter_work_dir() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    set_current_dir(dir.path()).unwrap();
+
+    dir
+}
+
+/// Gets the temporary folder the tests run from. Every test that reads or writes maps calls this first, and uses map names no other test uses.
+pub fn work_dir() -> &'static Path {
+    WORK_DIR.path()
+}
+
+/// Copies the files of a fixture map into the temporary folder, as the map with the given name.
+pub fn install_fixture(fixture: &str, map_name: &str) {
+    work_dir();
+
+    let source = format!("{}/tests/{fixture}", env!("CARGO_MANIFEST_DIR"));
+    let path = Map::path(map_name);
+    create_dir_all(&path).unwrap();
+
+    for file in read_dir(source).unwrap().flatten() {
+        copy(file.path(), path.join(file.file_name())).unwrap();
+    }
+}
@@ -1,6 +1,19 @@
-use automancy_defs::coord::TileCoord;
+use automancy_defs::coord::{ChunkCoord, TileCoord};
+use automancy_defs::id::{Id, Interner};
+use automancy_resources::data::inventory::Inventory;
+use automancy_resources::data::packed::{KeyLookup, KeyTable};
+use automancy_resources::data::DataMapRaw;
+use automancy_resources::worldgen::{Deposit, WorldGen};
 
+pub mod game;
+pub mod inspect;
 pub mod macros;
+pub mod map;
+pub mod migration;
+pub mod simulation;
+pub mod support;
+pub mod tile_entity;
+pub mod transit;
 
 #[test]
 fn test_tile_coord_serde() {
@@ -12,3 +25,134 @@ fn test_tile_coord_serde() {
 
     assert_eq!(c, deserialized);
 }
+
+#[test]
+fn test_chunk_coord_serde() {
+    let c = ChunkCoord::from(TileCoord::new(123, 456));
+
+    let serialized = serde_json::to_string(&c).unwrap();
+
+    let deserialized: ChunkCoord = serde_json::from_str(&serialized).unwrap();
+
+    assert_eq!(c, deserialized);
+}
+
+#[test]
+fn test_worldgen_seeded() {
+    let worldgen = WorldGen {
+        deposits: vec![Deposit {
+            tile: Id::from(1),
+            scale: 4.0,
+            threshold: 0.5,
+        }],
+    };
+    let chunk = ChunkCoord::from(TileCoord::new(40, -20));
+
+    assert_eq!(worldgen.generate(42, chunk), worldgen.generate(42, chunk));
+    assert_ne!(worldgen.generate(42, chunk), worldgen.generate(43, chunk));
+}
+
+#[test]
+fn test_key_table() {
+    let mut interner = Interner::new();
+    let coal = interner.get_or_intern("automancy:coal");
+    let iron = interner.get_or_intern("automancy:iron");
+
+    let mut keys = KeyTable::default();
+
+    assert_eq!(keys.key(&interner, coal), Some(0));
+    assert_eq!(keys.key(&interner, iron), Some(1));
+    assert_eq!(keys.key(&interner, coal), Some(0));
+
+    // each id is only taken once, by the first chunk that uses it
+    assert_eq!(keys.take_new(), vec!["automancy:coal", "automancy:iron"]);
+    assert_eq!(keys.key(&interner, iron), Some(1));
+    assert!(keys.take_new().is_empty());
+    assert_eq!(keys.key_raw("mod:gear"), 2);
+    assert_eq!(keys.take_new(), vec!["mod:gear"]);
+
+    let keys = keys.into_keys();
+    assert_eq!(keys.len(), 3);
+
+    // a game without iron loaded reads the key back as nothing
+    let mut other = Interner::new();
+    let coal = other.get_or_intern("automancy:coal");
+    let lookup = KeyLookup::new(&keys, &other);
+
+    assert_eq!(lookup.get(0), Some(coal));
+    assert_eq!(lookup.get(1), None);
+    assert_eq!(lookup.get(3), None);
+
+    // keys read bit by bit resolve the same way
+    let mut extended = KeyLookup::default();
+    extended.extend(&keys[..1], &other);
+    extended.extend(&keys[1..], &other);
+
+    assert_eq!(extended.get(0), Some(coal));
+    assert_eq!(extended.get(1), None);
+    assert_eq!(extended.names(), keys.as_slice());
+}
+
+#[test]
+fn test_emptied_stacks_are_removed() {
+    let resource_man = support::resource_man();
+    let gold = resource_man.interner.get("automancy:gold").unwrap();
+    let silver = resource_man.interner.get("automancy:silver").unwrap();
+    let coal = resource_man.interner.get("automancy:coal").unwrap();
+    let metals = resource_man.interner.get("automancy:#metals").unwrap();
+
+    let mut inventory = Inventory::default();
+    inventory.add(gold, 3);
+    inventory.add(silver, 3);
+    inventory.add(coal, 1);
+
+    // one of the metals runs out, and is not left behind as an empty stack
+    assert_eq!(inventory.take_matching(&resource_man, metals, 4), 0);
+    assert_eq!(inventory.count_matching(&resource_man, metals), 2);
+    assert_eq!(inventory.len(), 2);
+
+    assert_eq!(inventory.take(coal, 5), Some(1));
+    assert!(!inventory.contains_key(&coal));
+
+    // asking for more than is stored takes what there is
+    assert_eq!(inventory.take_matching(&resource_man, metals, 3), 2);
+    assert!(inventory.is_empty());
+}
+
+#[test]
+fn test_tags_in_a_cycle_are_dropped() {
+    let resource_man = support::resource_man();
+    let interner = &resource_man.interner;
+
+    // the test tags include each other, and loading goes on without them
+    for tag in ["test:#loop_a", "test:#loop_b"] {
+        let id = interner.get(tag).unwrap();
+        assert!(resource_man.registry.tag(id).is_none());
+    }
+
+    let metals = interner.get("automancy:#metals").unwrap();
+    assert!(resource_man.registry.tag(metals).is_some());
+}
+
+#[test]
+fn test_unknown_data_round_trip() {
+    let raw: DataMapRaw = serde_json::from_str(
+        r#"{
+            "automancy:target": { "Id": "mod:gadget" },
+            "automancy:buffer": { "Inventory": [["mod:gear", 3], ["automancy:coal", 1]] },
+            "mod:links": { "VecCoord": [[1, 2], [-3, 4]] },
+            "automancy:amount": { "Amount": 5 }
+        }"#,
+    )
+    .unwrap();
+
+    // the ids of tiles that are not loaded go through the key table as they are written
+    let mut keys = KeyTable::default();
+    let packed = raw.to_packed(&mut keys);
+    let unpacked = packed.to_raw(&keys.into_keys());
+
+    assert_eq!(
+        serde_json::to_value(&raw).unwrap(),
+        serde_json::to_value(&unpacked).unwrap()
+    );
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use automancy::game::GameMsg;
+use automancy::simulation::Simulation;
+use automancy::tile_entity::{TileEntity, TileEntityMsg, TRANSACTION_TIMEOUT};
+use automancy_defs::coord::TileCoord;
+use automancy_defs::hashbrown::HashSet;
+use automancy_resources::data::inventory::Inventory;
+use automancy_resources::data::stack::ItemStack;
+use automancy_resources::data::{Data, DataMap};
+use automancy_resources::ResourceManager;
+
+use crate::support::resource_man;
+
+fn machine(resource_man: &Arc<ResourceManager>, coord: TileCoord) -> TileEntity {
+    TileEntity {
+        id: resource_man
+            .interner
+            .get("automancy:basic_refinery")
+            .unwrap(),
+        coord,
+        tile_modifier: 0,
+        resource_man: resource_man.clone(),
+    }
+}
+
+/// Keeps track of the tiles that went to sleep, the way the game does.
+fn apply(sleeping: &mut HashSet<TileCoord>, messages: &[GameMsg]) {
+    for message in messages {
+        match message {
+            GameMsg::SleepTile(coord) => {
+                sleeping.insert(*coord);
+            }
+            GameMsg::WakeTile(coord) => {
+                sleeping.remove(coord);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_idle_machine_sleeps_until_woken() {
+    let resource_man = resource_man();
+    let data_ids = resource_man.registry.data_ids;
+    let coord = TileCoord::new(0, 0);
+
+    // the machine has a recipe and a target, but nothing to make the recipe from
+    let mut data = DataMap::default();
+    data.insert(data_ids.target, Data::Coord(TileCoord::new(1, 0)));
+    data.insert(
+        data_ids.script,
+        Data::Id(resource_man.interner.get("automancy:coal_to_fire").unwrap()),
+    );
+
+    let mut simulation = Simulation::default();
+    simulation.insert(machine(&resource_man, coord), data);
+
+    let mut sleeping = HashSet::new();
+
+    let messages = simulation.tick(0, &sleeping);
+    apply(&mut sleeping, &messages);
+    assert!(sleeping.contains(&coord));
+
+    // a sleeping tile is not ticked, so it has nothing to say
+    let messages = simulation.tick(1, &sleeping);
+    assert!(messages.is_empty());
+
+    // an item arriving wakes it up
+    let coal = resource_man.interner.get("automancy:coal").unwrap();
+    let messages = simulation.send(
+        coord,
+        TileEntityMsg::Transaction {
+            stack: ItemStack {
+                item: *resource_man.registry.item(coal).unwrap(),
+                amount: 1,
+            },
+            source_coord: TileCoord::new(-1, 0),
+            source_id: coal,
+            root_coord: TileCoord::new(-1, 0),
+            root_id: coal,
+        },
+    );
+    apply(&mut sleeping, &messages);
+    assert!(!sleeping.contains(&coord));
+
+    // and so does a change to its data, once it fell asleep again
+    simulation.send(coord, TileEntityMsg::RemoveData(data_ids.target));
+    let messages = simulation.tick(2, &sleeping);
+    apply(&mut sleeping, &messages);
+    assert!(sleeping.contains(&coord));
+
+    let messages = simulation.send(
+        coord,
+        TileEntityMsg::SetDataValue(data_ids.target, Data::Coord(TileCoord::new(1, 0))),
+    );
+    apply(&mut sleeping, &messages);
+    assert!(!sleeping.contains(&coord));
+}
+
+#[test]
+fn test_waiting_machine_asks_for_a_recount() {
+    let resource_man = resource_man();
+    let coord = TileCoord::new(0, 0);
+
+    let mut simulation = Simulation::default();
+    simulation.insert(machine(&resource_man, coord), DataMap::default());
+
+    // the machine still waits on an item it sent before the map was loaded, so it keeps ticking
+    simulation.send(coord, TileEntityMsg::InFlight(1));
+
+    let sleeping = HashSet::new();
+    let mut messages = vec![];
+
+    for tick_count in 0..TRANSACTION_TIMEOUT {
+        messages.extend(simulation.tick(tick_count, &sleeping));
+    }
+
+    // it asks the game how many are still on their way, instead of sending them again
+    assert!(messages
+        .iter()
+        .any(|message| matches!(message, GameMsg::CountInFlight(c) if *c == coord)));
+    assert!(!messages
+        .iter()
+        .any(|message| matches!(message, GameMsg::SleepTile(_) | GameMsg::SendItem { .. })));
+}
+
+#[test]
+fn test_working_machine_stays_awake() {
+    let resource_man = resource_man();
+    let data_ids = resource_man.registry.data_ids;
+    let coord = TileCoord::new(0, 0);
+
+    // the machine has what the recipe takes, so it is working on it
+    let mut buffer = Inventory::default();
+    buffer.add(resource_man.interner.get("automancy:coal").unwrap(), 1);
+
+    let mut data = DataMap::default();
+    data.insert(data_ids.target, Data::Coord(TileCoord::new(1, 0)));
+    data.insert(
+        data_ids.script,
+        Data::Id(resource_man.interner.get("automancy:coal_to_fire").unwrap()),
+    );
+    data.insert(data_ids.buffer, Data::Inventory(buffer));
+
+    let mut simulation = Simulation::default();
+    simulation.insert(machine(&resource_man, coord), data);
+
+    let mut sleeping = HashSet::new();
+
+    for tick_count in 0..5 {
+        let messages = simulation.tick(tick_count, &sleeping);
+        apply(&mut sleeping, &messages);
+        assert!(!sleeping.contains(&coord));
+    }
+
+    let data = simulation.data([coord]).remove(&coord).unwrap();
+    assert_eq!(
+        data.get(&data_ids.progress).and_then(Data::as_amount),
+        Some(&5)
+    );
+}
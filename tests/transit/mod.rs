@@ -0,0 +1,89 @@
+use automancy::transit::{ShipmentRaw, Transit};
+use automancy_defs::coord::TileCoord;
+use automancy_defs::id::IdRaw;
+
+use crate::support::resource_man;
+
+fn raw(item: &str, root_coord: TileCoord) -> ShipmentRaw {
+    ShipmentRaw {
+        from: TileCoord::new(0, 0),
+        to: TileCoord::new(1, 0),
+        remaining: 3,
+        item: IdRaw::parse(item),
+        amount: 2,
+        source_coord: root_coord,
+        source_id: IdRaw::parse("automancy:coal_deposit"),
+        root_coord,
+        root_id: IdRaw::parse("automancy:coal_deposit"),
+    }
+}
+
+#[test]
+fn test_unknown_shipments_are_saved_again() {
+    let resource_man = resource_man();
+    let root = TileCoord::new(-1, 0);
+
+    let transit = Transit::from_raw(
+        &[raw("automancy:coal", root), raw("mod:gear", root)],
+        &resource_man,
+    );
+
+    // only the shipment of a loaded item is on its way, but both are saved
+    assert_eq!(transit.len(), 1);
+
+    let saved = transit.to_raw(&resource_man.interner);
+    let mut items = saved
+        .iter()
+        .map(|shipment| shipment.item.to_string())
+        .collect::<Vec<_>>();
+    items.sort();
+
+    assert_eq!(items, vec!["automancy:coal", "mod:gear"]);
+}
+
+#[test]
+fn test_in_flight_counts_until_settled() {
+    let (a, b) = (TileCoord::new(0, 0), TileCoord::new(2, 0));
+
+    let mut transit = Transit::default();
+    transit.sent(a);
+    transit.sent(a);
+    transit.sent(b);
+
+    assert_eq!(transit.in_flight(a), 2);
+    assert_eq!(transit.in_flight(b), 1);
+    assert_eq!(transit.in_flight(TileCoord::new(5, 5)), 0);
+
+    // the count does not depend on where the items are, only on their results
+    transit.settled(a);
+    transit.settled(b);
+    transit.settled(b);
+
+    assert_eq!(transit.in_flight(a), 1);
+    assert_eq!(transit.in_flight(b), 0);
+
+    transit.forget(a);
+    assert_eq!(transit.in_flight(a), 0);
+    assert_eq!(transit.outstanding().count(), 0);
+}
+
+#[test]
+fn test_loaded_shipments_wait_on_results() {
+    let resource_man = resource_man();
+    let (a, b) = (TileCoord::new(-1, 0), TileCoord::new(-2, 0));
+
+    let transit = Transit::from_raw(
+        &[
+            raw("automancy:coal", a),
+            raw("automancy:coal", a),
+            raw("automancy:coal", b),
+            raw("mod:gear", b),
+        ],
+        &resource_man,
+    );
+
+    let mut counts = transit.outstanding().collect::<Vec<_>>();
+    counts.sort_by_key(|(coord, _)| coord.q());
+
+    assert_eq!(counts, vec![(b, 1), (a, 2)]);
+}